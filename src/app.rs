@@ -4,60 +4,102 @@ use eframe::{App, CreationContext, Frame, Storage};
 use eframe::epaint::Rgba;
 use egui::{CentralPanel, Context, Visuals};
 use egui_wgpu::RenderState;
+use instant::Instant;
 use triglyceride::{init_profiler, open_profiler};
 
-use crate::{get_mut, set_none_static};
 use crate::graph_editor::graph_editor::GraphEditor;
+use crate::path_tracer::render_utility::adapter_selection::restart_args;
 use crate::path_tracer::path_trace_renderer::PathTracerRenderer;
-use crate::singletons::settings::{SETTINGS, Settings};
-use crate::singletons::time_package::TIME;
+use crate::singletons::app_context::AppContext;
+use crate::user_interface::command_palette::CommandPalette;
+use crate::user_interface::theme_editor::ThemeEditor;
 use crate::user_interface::ui::UiState;
 
 init_profiler!(PROF, triglyceride::Settings::default());
 
 
 pub struct MgsApp {
+   pub context: AppContext,
    pub path_tracer: PathTracerRenderer,
    pub graph_editor: GraphEditor,
    pub ui_state: UiState,
+   pub command_palette: CommandPalette,
+   pub theme_editor: ThemeEditor,
 
    pub restart_queued: bool,
+   /// when the path tracer last rendered, the fps cap counts from here
+   last_render: Instant,
 }
 
 /// main functions
 impl MgsApp {
    pub fn new(cc: &CreationContext) -> Self {
-      // init none singletons
-      set_none_static!(SETTINGS => { Settings::new(cc) });
+      let context = AppContext::new(cc);
 
       // init packages
-      let path_tracer = PathTracerRenderer::new(cc);
+      let path_tracer = PathTracerRenderer::new(cc, &context.settings);
       let ui_state = UiState::new(cc);
 
       Self {
+         context,
          path_tracer,
-         graph_editor: GraphEditor {},
+         graph_editor: GraphEditor::new(),
          ui_state,
+         command_palette: CommandPalette::default(),
+         theme_editor: ThemeEditor::default(),
 
          restart_queued: false,
+         last_render: Instant::now(),
       }
    }
 
    /// global update inter-loop
    #[triglyceride::time_event(PROF, "APP_UPDATE")]
    pub fn update(&mut self, render_state: &RenderState) {
+      let _s = self.context.trace.scope("APP_UPDATE");
 
-      // update singletons
-      get_mut!(TIME).update();
+      // input still repaints the ui right away, the path tracer waits out the fps cap
+      if self.frame_interval().is_some_and(|interval| self.last_render.elapsed() < interval) {
+         return;
+      }
+      self.last_render = Instant::now();
+
+      self.context.update();
 
+      // the benchmark swaps the scene out and back, that isn't an edit
+      if !self.path_tracer.benchmark.is_running() {
+         self.context.history.update(&self.context.settings.current_scene);
+      }
 
       // update modules
-      self.path_tracer.update(render_state);
+      self.path_tracer.update(render_state, self.graph_editor.selected, &mut self.context);
    }
 
    pub fn restart(&mut self) {
       self.restart_queued = true;
    }
+
+   fn is_recording(&self) -> bool {
+      self.context.trace.is_recording() || self.path_tracer.benchmark.is_running()
+   }
+
+   /// the fps cap, capping a benchmark or trace would only measure the cap
+   fn frame_interval(&self) -> Option<Duration> {
+      self.context.settings.frame_pacing_settings.frame_interval().filter(|_| !self.is_recording())
+   }
+
+   /// repaints right away, after the fps cap or not at all when idle, egui still repaints on input
+   fn request_repaint(&mut self, ctx: &Context) {
+      if !self.is_recording() && !self.path_tracer.wants_repaint(&self.context.settings) {
+         self.context.time.idle();
+         return;
+      }
+
+      match self.frame_interval() {
+         Some(interval) => ctx.request_repaint_after(interval.saturating_sub(self.last_render.elapsed())),
+         None => ctx.request_repaint(),
+      }
+   }
 }
 
 
@@ -65,10 +107,16 @@ impl MgsApp {
 impl App for MgsApp {
    #[triglyceride::time_event(PROF, "EFRAME_UPDATE")]
    fn update(&mut self, ctx: &Context, frame: &mut Frame) {
+      // dropped after ``end_frame``, lands in the trace next frame
+      let _s = self.context.trace.scope("EFRAME_UPDATE");
+
       self.update(frame.wgpu_render_state().expect("Failed to unwrap render state"));
 
+      self.handle_shortcuts(ctx);
+
       // overload panel
       triglyceride::time_event_mac!(PROF, "UI_UPDATE", {
+         let _s = self.context.trace.scope("UI_UPDATE");
          CentralPanel::default()
              .show(ctx, |ui| {
                 self.ui(ui);
@@ -79,13 +127,20 @@ impl App for MgsApp {
          p.set_constant_reference("OVERALL")
       });
 
-      ctx.request_repaint();
+      self.request_repaint(ctx);
+
+      self.context.trace.end_frame();
+
+      // command line benchmarks quit once the report is written, without saving the benchmark scene
+      if let Some(code) = self.path_tracer.benchmark.exit_code() {
+         std::process::exit(code);
+      }
 
       if self.restart_queued {
          if let Some(s) = frame.storage_mut() {
             self.restart_queued = false;
             self.save(s);
-            restart_app();
+            restart_app(&restart_args(&self.context.settings.adapter_settings));
          }
       }
    }
@@ -93,7 +148,11 @@ impl App for MgsApp {
    fn save(&mut self, storage: &mut dyn Storage) {
       self.graph_editor.save(storage);
       self.ui_state.save(storage);
-      get_mut!(SETTINGS).save(storage);
+
+      // the settings hold the benchmark scene while one runs
+      if !self.path_tracer.benchmark.is_running() {
+         self.context.save(storage);
+      }
    }
 
    fn auto_save_interval(&self) -> Duration {
@@ -107,15 +166,16 @@ impl App for MgsApp {
 
 
 #[cfg(not(target_arch = "wasm32"))]
-fn restart_app() {
+fn restart_app(args: &[String]) {
    use std::process::Command;
    let mut cmd = Command::new(std::env::current_exe().unwrap());
+   cmd.args(args);
    let _ = cmd.spawn();
    std::process::exit(0);
 }
 
 #[cfg(target_arch = "wasm32")]
-fn restart_app() {
+fn restart_app(_args: &[String]) {
    // todo doesn't work
    use wasm_bindgen::prelude::*;
    #[wasm_bindgen]
@@ -0,0 +1,132 @@
+//////////////////
+// Vec3 helpers //
+//////////////////
+// small cpu side mirror of the glsl vector ops, just enough for picking and projection
+
+pub fn add3(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+   [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub fn sub3(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+   [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn scale3(a: [f32; 3], s: f32) -> [f32; 3] {
+   [a[0] * s, a[1] * s, a[2] * s]
+}
+
+pub fn dot3(a: [f32; 3], b: [f32; 3]) -> f32 {
+   a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn cross3(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+   [
+      a[1] * b[2] - a[2] * b[1],
+      a[2] * b[0] - a[0] * b[2],
+      a[0] * b[1] - a[1] * b[0],
+   ]
+}
+
+pub fn length3(a: [f32; 3]) -> f32 {
+   dot3(a, a).sqrt()
+}
+
+pub fn normalize3(a: [f32; 3]) -> [f32; 3] {
+   let l = length3(a);
+   if l > 0.0 { scale3(a, 1.0 / l) } else { a }
+}
+
+pub fn abs3(a: [f32; 3]) -> [f32; 3] {
+   [a[0].abs(), a[1].abs(), a[2].abs()]
+}
+
+pub fn max3(a: [f32; 3], v: f32) -> [f32; 3] {
+   [a[0].max(v), a[1].max(v), a[2].max(v)]
+}
+
+/// same as ``rot3D`` in the shaders, glsl matrices are column major so
+/// each axis is applied in the order x -> y -> z
+pub fn rot3(p: [f32; 3], rot: [f32; 3]) -> [f32; 3] {
+   let (sx, cx) = rot[0].sin_cos();
+   let (sy, cy) = rot[1].sin_cos();
+   let (sz, cz) = rot[2].sin_cos();
+
+   let p = [p[0], cx * p[1] + sx * p[2], -sx * p[1] + cx * p[2]];
+   let p = [cy * p[0] - sy * p[2], p[1], sy * p[0] + cy * p[2]];
+   [cz * p[0] + sz * p[1], -sz * p[0] + cz * p[1], p[2]]
+}
+
+/// inverse of ``rot3``
+pub fn inv_rot3(p: [f32; 3], rot: [f32; 3]) -> [f32; 3] {
+   let (sx, cx) = rot[0].sin_cos();
+   let (sy, cy) = rot[1].sin_cos();
+   let (sz, cz) = rot[2].sin_cos();
+
+   let p = [cz * p[0] - sz * p[1], sz * p[0] + cz * p[1], p[2]];
+   let p = [cy * p[0] + sy * p[2], p[1], -sy * p[0] + cy * p[2]];
+   [p[0], cx * p[1] - sx * p[2], sx * p[1] + cx * p[2]]
+}
+
+
+//////////////////
+// Mat3 helpers //
+//////////////////
+// row major, ``mat3_from_euler`` gives the same matrix ``rot3`` applies
+
+pub fn mat3_mul_vec(m: [[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+   [dot3(m[0], v), dot3(m[1], v), dot3(m[2], v)]
+}
+
+pub fn mat3_mul(a: [[f32; 3]; 3], b: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+   let mut out = [[0.0; 3]; 3];
+   for (r, row) in out.iter_mut().enumerate() {
+      for (c, v) in row.iter_mut().enumerate() {
+         *v = a[r][0] * b[0][c] + a[r][1] * b[1][c] + a[r][2] * b[2][c];
+      }
+   }
+   out
+}
+
+pub fn mat3_from_euler(rot: [f32; 3]) -> [[f32; 3]; 3] {
+   let cols = [
+      rot3([1.0, 0.0, 0.0], rot),
+      rot3([0.0, 1.0, 0.0], rot),
+      rot3([0.0, 0.0, 1.0], rot),
+   ];
+   mat3_transpose(cols)
+}
+
+pub fn mat3_transpose(m: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+   [
+      [m[0][0], m[1][0], m[2][0]],
+      [m[0][1], m[1][1], m[2][1]],
+      [m[0][2], m[1][2], m[2][2]],
+   ]
+}
+
+/// inverse of ``mat3_from_euler``
+pub fn mat3_to_euler(m: [[f32; 3]; 3]) -> [f32; 3] {
+   let y = m[2][0].clamp(-1.0, 1.0).asin();
+
+   if y.cos().abs() > 1e-5 {
+      let x = (-m[2][1]).atan2(m[2][2]);
+      let z = (-m[1][0]).atan2(m[0][0]);
+      [x, y, z]
+   } else {
+      // gimbal lock, put everything into z
+      [0.0, y, m[0][1].atan2(m[1][1])]
+   }
+}
+
+/// rotation of ``angle`` radians around ``axis`` (right hand rule)
+pub fn mat3_axis_angle(axis: [f32; 3], angle: f32) -> [[f32; 3]; 3] {
+   let [x, y, z] = normalize3(axis);
+   let (s, c) = angle.sin_cos();
+   let t = 1.0 - c;
+
+   [
+      [t * x * x + c, t * x * y - s * z, t * x * z + s * y],
+      [t * x * y + s * z, t * y * y + c, t * y * z - s * x],
+      [t * x * z - s * y, t * y * z + s * x, t * z * z + c],
+   ]
+}
@@ -0,0 +1,69 @@
+/// fixed size buffer that overwrites its oldest value once full, nothing is moved or reallocated per push
+pub struct RingBuffer<T> {
+   data: Vec<T>,
+   capacity: usize,
+   /// next slot written once full, also where the oldest value is
+   head: usize,
+}
+
+impl<T: Copy> RingBuffer<T> {
+   pub fn new(capacity: usize) -> Self {
+      Self {
+         data: Vec::with_capacity(capacity.max(1)),
+         capacity: capacity.max(1),
+         head: 0,
+      }
+   }
+
+   pub fn push(&mut self, value: T) {
+      if self.data.len() < self.capacity {
+         self.data.push(value);
+      } else {
+         self.data[self.head] = value;
+         self.head = (self.head + 1) % self.capacity;
+      }
+   }
+
+   /// oldest first
+   pub fn iter(&self) -> impl Iterator<Item = &T> {
+      let (newer, older) = self.data.split_at(self.head);
+      older.iter().chain(newer)
+   }
+
+   pub fn latest(&self) -> Option<T> {
+      match self.data.len() {
+         0 => None,
+         len if len < self.capacity => Some(self.data[len - 1]),
+         _ => Some(self.data[(self.head + self.capacity - 1) % self.capacity]),
+      }
+   }
+
+   pub fn len(&self) -> usize {
+      self.data.len()
+   }
+
+   pub fn is_empty(&self) -> bool {
+      self.data.is_empty()
+   }
+
+   pub fn capacity(&self) -> usize {
+      self.capacity
+   }
+
+   /// keeps the newest values that still fit
+   pub fn set_capacity(&mut self, capacity: usize) {
+      let capacity = capacity.max(1);
+      if capacity == self.capacity { return; }
+
+      let kept = self.iter().copied().collect::<Vec<_>>();
+      let skip = kept.len().saturating_sub(capacity);
+
+      *self = Self::new(capacity);
+      kept.into_iter().skip(skip).for_each(|value| self.push(value));
+   }
+
+   pub fn clear(&mut self) {
+      self.data.clear();
+      self.head = 0;
+   }
+}
@@ -1,16 +1,121 @@
 use eframe::Storage;
+use egui::{DragValue, ScrollArea, TextEdit, Ui};
 
-pub struct GraphEditor {}
+use crate::singletons::scene::{MAX_OBJECTS, Scene, SceneObject};
+use crate::user_interface::ui_modules::enum_combination_box;
+
+pub struct GraphEditor {
+   /// selected node, set from here or by clicking in the viewport
+   pub selected: Option<usize>,
+   shown_selection: Option<usize>,
+}
 impl GraphEditor {
 
    pub fn new() -> Self {
-      Self {}
+      Self {
+         selected: None,
+         shown_selection: None,
+      }
    }
 
    pub fn update(&mut self) {}
 
-   pub fn ui(&mut self) {}
+   pub fn ui(&mut self, ui: &mut Ui, scene: &mut Scene) {
+      // objects can be removed while selected
+      if self.selected.is_some_and(|i| i >= scene.objects.len()) {
+         self.selected = None;
+      }
+
+      ui.horizontal_top(|ui| {
+         ui.group(|ui| {
+            ui.vertical(|ui| {
+               ui.set_width(200.0);
+               self.node_list(ui, scene);
+            });
+         });
+
+         if let Some(index) = self.selected {
+            ui.group(|ui| {
+               ui.vertical(|ui| {
+                  node_properties(ui, &mut scene.objects[index]);
+               });
+            });
+         }
+      });
+
+      self.shown_selection = self.selected;
+   }
+
+   fn node_list(&mut self, ui: &mut Ui, scene: &mut Scene) {
+      ui.horizontal(|ui| {
+         ui.label("Nodes");
+
+         let can_add = scene.objects.len() < MAX_OBJECTS;
+         if ui.add_enabled(can_add, egui::Button::new("+")).clicked() {
+            scene.objects.push(SceneObject::default());
+            self.selected = Some(scene.objects.len() - 1);
+         }
+
+         if ui.add_enabled(self.selected.is_some(), egui::Button::new("-")).clicked() {
+            if let Some(index) = self.selected.take() {
+               scene.objects.remove(index);
+            }
+         }
+      });
+      ui.separator();
+
+      ScrollArea::vertical().show(ui, |ui| {
+         for (i, object) in scene.objects.iter().enumerate() {
+            let is_selected = self.selected == Some(i);
+            let response = ui.selectable_label(is_selected, format!("{i}: {}", object.name));
+
+            if response.clicked() {
+               self.selected = if is_selected { None } else { Some(i) };
+            }
+
+            // selection came from somewhere else, e.g. the viewport
+            if is_selected && self.shown_selection != self.selected {
+               response.scroll_to_me(None);
+            }
+         }
+      });
+   }
 
    pub fn save(&mut self, _storage: &mut dyn Storage) {}
 
-}
\ No newline at end of file
+}
+
+fn node_properties(ui: &mut Ui, object: &mut SceneObject) {
+   ui.add(TextEdit::singleline(&mut object.name).desired_width(150.0));
+   enum_combination_box(ui, &mut object.shape, "Shape");
+
+   ui.horizontal(|ui| {
+      ui.label("Data");
+      for v in object.data.iter_mut() {
+         ui.add(DragValue::new(v).speed(0.01));
+      }
+   });
+
+   let tr = &mut object.transform;
+   ui.horizontal(|ui| {
+      ui.label("Position");
+      ui.add(DragValue::new(&mut tr.position[0]).range(-100.0..=100.0).speed(0.01).prefix("X: "));
+      ui.add(DragValue::new(&mut tr.position[1]).range(-100.0..=100.0).speed(0.01).prefix("Y: "));
+      ui.add(DragValue::new(&mut tr.position[2]).range(-100.0..=100.0).speed(0.01).prefix("Z: "));
+   });
+   ui.horizontal(|ui| {
+      ui.label("Rotation");
+      ui.add(DragValue::new(&mut tr.rotation[0]).speed(0.01).prefix("X: "));
+      ui.add(DragValue::new(&mut tr.rotation[1]).speed(0.01).prefix("Y: "));
+      ui.add(DragValue::new(&mut tr.rotation[2]).speed(0.01).prefix("Z: "));
+   });
+   ui.horizontal(|ui| {
+      ui.label("Scale");
+      ui.add(DragValue::new(&mut tr.scale).range(0.001..=100.0).speed(0.01));
+   });
+
+   ui.horizontal(|ui| {
+      ui.label("Color");
+      ui.color_edit_button_rgb(&mut object.color);
+   });
+}
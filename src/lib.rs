@@ -19,14 +19,22 @@ pub mod global_utility {
 }
 
 pub mod user_interface {
+   pub mod actions;
+   pub mod command_palette;
+   pub mod gizmo;
+   pub mod keymap;
+   pub mod theme_editor;
    pub mod ui;
    pub mod ui_modules;
 }
 
 pub mod singletons {
+   pub mod app_context;
    pub mod scene;
+   pub mod scene_history;
    pub mod settings;
    pub mod time_package;
+   pub mod trace_recorder;
 }
 
 pub mod graph_editor {
@@ -34,10 +42,19 @@ pub mod graph_editor {
 }
 
 pub mod path_tracer {
+   pub mod benchmark;
+   pub mod cpu_raymarcher;
+   pub mod denoise_pipeline;
+   pub mod frame_budget;
    pub mod display_texture_pipeline;
    pub mod path_tracer_package;
    pub mod path_trace_renderer;
+   pub mod render_queue;
+   pub mod tiled_renderer;
    pub mod render_utility {
+      pub mod adapter_selection;
+      pub mod aov_buffer_package;
+      pub mod device_features;
       pub mod dual_storage_texture_package;
       pub mod helper_structs;
       pub mod vertex_package;
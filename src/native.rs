@@ -1,34 +1,32 @@
 use std::sync::Arc;
-use wgpu::{Adapter, DeviceDescriptor, Features, Limits, PresentMode};
+use wgpu::{Adapter, DeviceDescriptor};
 
 /// Native
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result {
    use egui_wgpu::WgpuConfiguration;
    use mgsdfe::MgsApp;
+   use mgsdfe::path_tracer::render_utility::adapter_selection::{launch_settings, stored_settings, APP_ID};
+   use mgsdfe::path_tracer::render_utility::device_features::device_descriptor;
 
    println!("remember to hide console in releases");
 
    env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
-   let device_descriptor_fn: Arc<dyn Fn(&Adapter) -> DeviceDescriptor<'static>> = Arc::new(|_adapter: &Adapter| {
-      DeviceDescriptor {
-         label: Some("wgpu native device desc"),
-         required_features: Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES |
-             Features::TIMESTAMP_QUERY_INSIDE_PASSES |
-             Features::TIMESTAMP_QUERY |
-             Features::TIMESTAMP_QUERY_INSIDE_ENCODERS,
-         required_limits: Limits::default(),
-      }
-   });
+   let device_descriptor_fn: Arc<dyn Fn(&Adapter) -> DeviceDescriptor<'static>> = Arc::new(device_descriptor);
+
+   // the adapter is picked before the app exists so its settings are read here
+   let adapter_settings = launch_settings(stored_settings());
+   let default_wgpu = WgpuConfiguration::default();
 
    let native_options = eframe::NativeOptions {
-      vsync: false,
+      vsync: adapter_settings.present_mode.is_vsync(),
       wgpu_options: WgpuConfiguration {
-         present_mode: PresentMode::Immediate,
-         power_preference: wgpu::PowerPreference::HighPerformance,
+         present_mode: adapter_settings.present_mode.mode(),
+         supported_backends: adapter_settings.backend.backends().unwrap_or(default_wgpu.supported_backends),
+         power_preference: adapter_settings.power_preference.preference(),
          device_descriptor: device_descriptor_fn,
-         ..Default::default()
+         ..default_wgpu
       },
       viewport: egui::ViewportBuilder::default()
           .with_inner_size([400.0, 300.0])
@@ -37,7 +35,7 @@ fn main() -> eframe::Result {
    };
 
    eframe::run_native(
-      "eframe template",
+      APP_ID,
       native_options,
       Box::new(|cc| Ok(Box::new(MgsApp::new(cc)))),
    )
@@ -0,0 +1,475 @@
+use std::collections::BTreeMap;
+use std::f32::consts::TAU;
+
+use egui::{ComboBox, DragValue, ProgressBar, Ui};
+use instant::Instant;
+
+use crate::path_tracer::render_utility::adapter_selection::Adapters;
+use crate::path_tracer::render_utility::gpu_profiler::GpuFrame;
+use crate::singletons::app_context::AppContext;
+use crate::singletons::scene::{Camera, CameraView, Scene, SceneObject, ShapeType, Transform};
+use crate::singletons::settings::Settings;
+use crate::singletons::time_package::FrameStats;
+
+/// rendered before recording so pipeline creation and first uploads don't count
+const WARMUP_FRAMES: u32 = 30;
+/// kept rendering after the last frame so the gpu timings of it can be read back
+const GPU_LAG_FRAMES: u32 = 8;
+/// same as the stats page default
+const STUTTER_FACTOR: f64 = 2.0;
+
+/// built in scenes, anything ending in ``.json`` is loaded as a saved ``Scene`` instead
+pub const BENCHMARK_SCENES: [&str; 3] = ["default", "shapes", "mandelbulb"];
+
+#[derive(Clone)]
+pub struct BenchmarkSettings {
+   pub scene: String,
+   pub frames: u32,
+   pub width: u32,
+   pub height: u32,
+   pub output_path: String,
+   /// quits once the report is written, for runs started from the command line
+   pub exit_when_done: bool,
+}
+
+impl Default for BenchmarkSettings {
+   fn default() -> Self {
+      Self {
+         scene: BENCHMARK_SCENES[0].to_string(),
+         frames: 600,
+         width: 1280,
+         height: 720,
+         output_path: "benchmark.json".to_string(),
+         exit_when_done: false,
+      }
+   }
+}
+
+impl BenchmarkSettings {
+   /// ``--benchmark <scene> [--frames n] [--resolution wxh] [--output path]``, none without ``--benchmark``
+   pub fn from_args() -> Option<Self> {
+      let args = std::env::args().collect::<Vec<_>>();
+      let value = |flag: &str| args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1));
+
+      let mut settings = Self {
+         scene: value("--benchmark")?.clone(),
+         exit_when_done: true,
+         ..Self::default()
+      };
+
+      if let Some(frames) = value("--frames").and_then(|f| f.parse().ok()) {
+         settings.frames = frames;
+      }
+      if let Some((width, height)) = value("--resolution").and_then(|r| r.split_once('x')) {
+         settings.width = width.parse().unwrap_or(settings.width);
+         settings.height = height.parse().unwrap_or(settings.height);
+      }
+      if let Some(output) = value("--output") {
+         settings.output_path = output.clone();
+      }
+
+      Some(settings)
+   }
+}
+
+/// plays a camera path through a scene for a fixed number of frames at a fixed
+/// resolution and writes the frame and gpu timings to a json report
+pub struct Benchmark {
+   pub settings: BenchmarkSettings,
+   start_requested: bool,
+   cancel_requested: bool,
+   run: Option<Run>,
+   /// process exit code once a command line run is done
+   exit_code: Option<i32>,
+   status: String,
+}
+
+struct Run {
+   /// keyframes looped through over the run, from the scene's cameras
+   path: Vec<CameraView>,
+   /// what the user had, put back once done
+   restore: Box<Settings>,
+   phase: Phase,
+   /// recorded frames so far
+   frame: u32,
+   /// every frame of the run, warmup and finishing included, moves the camera
+   tick: u32,
+   start: Instant,
+   end: Option<Instant>,
+
+   frame_times: Vec<f64>,
+   gpu_times: BTreeMap<String, Vec<f64>>,
+}
+
+#[derive(PartialEq)]
+enum Phase {
+   Warmup(u32),
+   Running,
+   Finishing(u32),
+}
+
+impl Benchmark {
+   pub fn new() -> Self {
+      let from_args = BenchmarkSettings::from_args();
+
+      Self {
+         start_requested: from_args.is_some(),
+         cancel_requested: false,
+         settings: from_args.unwrap_or_default(),
+         run: None,
+         exit_code: None,
+         status: String::new(),
+      }
+   }
+
+   pub fn is_running(&self) -> bool {
+      self.run.is_some()
+   }
+
+   /// some once a command line run has written its report, or failed to
+   pub fn exit_code(&self) -> Option<i32> {
+      self.exit_code
+   }
+
+   /// swaps in the benchmark scene, the user's settings are kept to put back after
+   fn start(&mut self, settings: &mut Settings) -> Result<(), String> {
+      let scene = load_scene(&self.settings.scene)?;
+
+      let mut path = scene.cameras.iter().map(|camera| camera.view).collect::<Vec<_>>();
+      if path.is_empty() {
+         path.push(CameraView::from_settings(&scene.parthtrace_settings));
+      }
+
+      let restore = Box::new(settings.clone());
+
+      settings.current_scene = scene;
+      settings.image_size_settings.width = self.settings.width.max(1);
+      settings.image_size_settings.height = self.settings.height.max(1);
+      settings.dynamic_resolution_settings.enabled = false;
+      settings.frame_budget_settings.enabled = false;
+
+      self.run = Some(Run {
+         path,
+         restore,
+         phase: Phase::Warmup(WARMUP_FRAMES),
+         frame: 0,
+         tick: 0,
+         start: Instant::now(),
+         end: None,
+         frame_times: Vec::with_capacity(self.settings.frames as usize),
+         gpu_times: BTreeMap::new(),
+      });
+      self.status = format!("Running {}", self.settings.scene);
+      Ok(())
+   }
+
+   /// once per frame before the scene is uploaded, moves the camera and records the last frame
+   pub fn update(&mut self, context: &mut AppContext, gpu_timestamps: bool) {
+      if self.start_requested {
+         self.start_requested = false;
+         if let Err(e) = self.start(&mut context.settings) {
+            self.finish_with(format!("Couldn't start the benchmark: {e}"), 1);
+         }
+      }
+
+      if self.cancel_requested {
+         self.cancel_requested = false;
+         if let Some(run) = self.run.take() {
+            context.settings = *run.restore;
+            self.status = "Cancelled".to_string();
+         }
+      }
+
+      if self.run.as_ref().is_some_and(|run| run.phase == Phase::Finishing(0)) {
+         let result = self.write_report(&context.adapters, gpu_timestamps);
+         context.settings = *self.run.take().unwrap().restore;
+
+         match result {
+            Ok(()) => self.finish_with(format!("Saved the report to {}", self.settings.output_path), 0),
+            Err(e) => self.finish_with(format!("Couldn't save the report: {e}"), 1),
+         }
+         return;
+      }
+
+      let frames = self.settings.frames.max(1);
+      let Some(run) = &mut self.run else { return; };
+
+      match &mut run.phase {
+         Phase::Warmup(0) => {
+            run.phase = Phase::Running;
+            run.start = Instant::now();
+         }
+         Phase::Warmup(left) | Phase::Finishing(left) => *left -= 1,
+         Phase::Running => {
+            run.frame_times.push(context.time.delta_time * 1000.0);
+            run.frame += 1;
+
+            if run.frame >= frames {
+               run.phase = Phase::Finishing(GPU_LAG_FRAMES);
+               run.end = Some(Instant::now());
+            }
+         }
+      }
+
+      // the camera keeps moving through warmup and finishing so every frame does the same work,
+      // offset so the recorded frames go around the path once from the first camera
+      let recorded = run.tick as f32 - (WARMUP_FRAMES + 1) as f32;
+      run.tick += 1;
+      let t = (recorded / frames as f32 * run.path.len() as f32).rem_euclid(run.path.len() as f32);
+      let segment = t as usize % run.path.len();
+      let next = (segment + 1) % run.path.len();
+      run.path[segment].lerp(&run.path[next], t.fract()).apply(&mut context.settings.current_scene.parthtrace_settings);
+   }
+
+   /// gpu frames from ``GpuProfiler::take_captured``, the ones rendered while recording are kept
+   pub fn gpu_frames(&mut self, frames: &[GpuFrame]) {
+      let Some(run) = &mut self.run else { return; };
+      if matches!(run.phase, Phase::Warmup(_)) { return; }
+
+      for frame in frames {
+         if frame.submitted < run.start || run.end.is_some_and(|end| frame.submitted > end) { continue; }
+
+         for scope in &frame.scopes {
+            run.gpu_times.entry(scope.key.clone()).or_default().push(scope.time_ms);
+         }
+      }
+   }
+
+   fn finish_with(&mut self, status: String, exit_code: i32) {
+      if exit_code != 0 {
+         log::error!("{status}");
+      } else {
+         log::info!("{status}");
+      }
+
+      if self.settings.exit_when_done {
+         self.exit_code = Some(exit_code);
+      }
+      self.status = status;
+   }
+
+   fn write_report(&self, adapters: &Adapters, gpu_timestamps: bool) -> std::io::Result<()> {
+      let run = self.run.as_ref().unwrap();
+      let seconds = run.end.unwrap_or_else(Instant::now).duration_since(run.start).as_secs_f64();
+
+      let report = Report {
+         version: env!("CARGO_PKG_VERSION"),
+         scene: &self.settings.scene,
+         frames: run.frame,
+         width: self.settings.width,
+         height: self.settings.height,
+         seconds,
+         average_fps: run.frame as f64 / seconds,
+         adapter: adapters.current.as_ref().map(|adapter| AdapterReport {
+            name: adapter.info.name.clone(),
+            backend: format!("{:?}", adapter.info.backend),
+            device_type: format!("{:?}", adapter.info.device_type),
+            driver: adapter.info.driver.clone(),
+            driver_info: adapter.info.driver_info.clone(),
+            vendor: adapter.info.vendor,
+            device: adapter.info.device,
+         }),
+         frame_times: FrameStats::from_times(run.frame_times.clone(), STUTTER_FACTOR).unwrap_or_default(),
+         gpu_timestamps,
+         gpu: run.gpu_times.iter().filter_map(|(key, times)| Some((key.as_str(), ScopeReport::new(times)?))).collect(),
+      };
+
+      let file = std::fs::File::create(&self.settings.output_path)?;
+      serde_json::to_writer_pretty(std::io::BufWriter::new(file), &report).map_err(std::io::Error::other)
+   }
+}
+
+impl Default for Benchmark {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+
+
+////////////
+// Report //
+////////////
+#[derive(serde::Serialize)]
+struct Report<'a> {
+   version: &'static str,
+   scene: &'a str,
+   frames: u32,
+   width: u32,
+   height: u32,
+   seconds: f64,
+   average_fps: f64,
+   adapter: Option<AdapterReport>,
+   /// milliseconds
+   frame_times: FrameStats,
+   /// false when the device can't do timestamp queries, ``gpu`` is empty then
+   gpu_timestamps: bool,
+   gpu: BTreeMap<&'a str, ScopeReport>,
+}
+
+#[derive(serde::Serialize)]
+struct AdapterReport {
+   name: String,
+   backend: String,
+   device_type: String,
+   driver: String,
+   driver_info: String,
+   vendor: u32,
+   device: u32,
+}
+
+#[derive(serde::Serialize)]
+struct ScopeReport {
+   mean_ms: f64,
+   p50_ms: f64,
+   p95_ms: f64,
+   max_ms: f64,
+   samples: usize,
+}
+
+impl ScopeReport {
+   fn new(times: &[f64]) -> Option<Self> {
+      let stats = FrameStats::from_times(times.to_vec(), STUTTER_FACTOR)?;
+
+      Some(Self {
+         mean_ms: times.iter().sum::<f64>() / times.len() as f64,
+         p50_ms: stats.p50,
+         p95_ms: stats.p95,
+         max_ms: stats.max,
+         samples: times.len(),
+      })
+   }
+}
+
+
+////////////
+// Scenes //
+////////////
+fn load_scene(name: &str) -> Result<Scene, String> {
+   if name.ends_with(".json") {
+      let text = std::fs::read_to_string(name).map_err(|e| format!("{name}: {e}"))?;
+      return serde_json::from_str(&text).map_err(|e| format!("{name}: {e}"));
+   }
+
+   let (objects, radius) = match name {
+      "default" => (vec![SceneObject::default()], 4.0),
+      "shapes" => (shape_ring(), 9.0),
+      "mandelbulb" => (vec![SceneObject {
+         name: "mandelbulb".to_string(),
+         shape: ShapeType::Mandelbulb,
+         data: [8.0, 0.0, 0.0],
+         ..SceneObject::default()
+      }], 3.0),
+      _ => return Err(format!("no scene called {name}, try one of {BENCHMARK_SCENES:?} or a .json scene")),
+   };
+
+   let mut scene = Scene {
+      objects,
+      ..Scene::default()
+   };
+   scene.cameras = orbit(&scene, radius);
+   scene.active_camera = None;
+   Ok(scene)
+}
+
+/// one of each shape in a circle, cycling through the shape types
+fn shape_ring() -> Vec<SceneObject> {
+   const COUNT: usize = 8;
+   let shapes = [ShapeType::Sphere, ShapeType::Cube, ShapeType::Octahedron, ShapeType::Mandelbulb];
+
+   (0..COUNT).map(|i| {
+      let angle = i as f32 / COUNT as f32 * TAU;
+      let shape = shapes[i % shapes.len()];
+
+      SceneObject {
+         name: format!("{shape:?} {i}"),
+         shape,
+         data: if shape == ShapeType::Mandelbulb { [8.0, 0.0, 0.0] } else { [0.8, 0.8, 0.8] },
+         transform: Transform {
+            position: [angle.sin() * 4.0, 0.0, angle.cos() * 4.0],
+            ..Transform::default()
+         },
+         color: [0.4 + 0.5 * angle.sin().abs(), 0.6, 0.4 + 0.5 * angle.cos().abs()],
+      }
+   }).collect()
+}
+
+/// four cameras a quarter turn apart looking at the origin from slightly above
+fn orbit(scene: &Scene, radius: f32) -> Vec<Camera> {
+   let height = radius * 0.3;
+   let pitch = -height.atan2(radius);
+   let base = CameraView::from_settings(&scene.parthtrace_settings);
+
+   (0..4).map(|i| {
+      let yaw = i as f32 * TAU / 4.0;
+
+      Camera {
+         name: format!("Orbit {}", i + 1),
+         view: CameraView {
+            position: [radius * yaw.sin(), height, -radius * yaw.cos()],
+            rotation: [pitch, yaw, 0.0],
+            ..base
+         },
+      }
+   }).collect()
+}
+
+
+////////
+// Ui //
+////////
+impl Benchmark {
+   pub fn ui(&mut self, ui: &mut Ui) {
+      ui.heading("Benchmark");
+
+      // reports are written to a file
+      if cfg!(target_arch = "wasm32") {
+         ui.label("Only available on desktop");
+         return;
+      }
+
+      ui.add_enabled_ui(!self.is_running(), |ui| {
+         ComboBox::from_label("Scene")
+             .selected_text(&self.settings.scene)
+             .show_ui(ui, |ui| {
+                for scene in BENCHMARK_SCENES {
+                   ui.selectable_value(&mut self.settings.scene, scene.to_string(), scene);
+                }
+             });
+         ui.horizontal(|ui| {
+            ui.label("Or a scene file");
+            ui.text_edit_singleline(&mut self.settings.scene);
+         });
+
+         ui.horizontal(|ui| {
+            ui.add(DragValue::new(&mut self.settings.width).range(1..=16384).prefix("W: "));
+            ui.add(DragValue::new(&mut self.settings.height).range(1..=16384).prefix("H: "));
+            ui.add(DragValue::new(&mut self.settings.frames).range(1..=100000).prefix("Frames: "));
+         });
+
+         ui.horizontal(|ui| {
+            ui.label("Output");
+            ui.text_edit_singleline(&mut self.settings.output_path);
+         });
+      });
+
+      match &self.run {
+         None => {
+            if ui.button("Run").clicked() { self.start_requested = true; }
+         }
+         Some(run) => {
+            let (done, text) = match run.phase {
+               Phase::Warmup(_) => (0.0, "Warming up".to_string()),
+               Phase::Running => (run.frame as f32 / self.settings.frames.max(1) as f32, format!("Frame {}/{}", run.frame, self.settings.frames)),
+               Phase::Finishing(_) => (1.0, "Waiting on the gpu".to_string()),
+            };
+            ui.add(ProgressBar::new(done).text(text));
+
+            if ui.button("Cancel").clicked() { self.cancel_requested = true; }
+         }
+      }
+
+      if !self.status.is_empty() {
+         ui.label(&self.status);
+      }
+   }
+}
@@ -0,0 +1,222 @@
+use std::f32::consts::PI;
+
+use crate::global_utility::functions::{abs3, add3, inv_rot3, length3, max3, normalize3, rot3, scale3, sub3};
+use crate::singletons::scene::{ParthtracerSettings, Projection, Scene, SceneObject, ShapeType};
+
+/// has to match ``FP`` and ``MHD`` in the shader
+const FP: f32 = 200.0;
+const MHD: f32 = 0.001;
+
+// cpu side copy of the shaders raymarcher, used for things that need
+// to know what is under a pixel without reading back from the gpu
+
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+   pub ro: [f32; 3],
+   pub rd: [f32; 3],
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Hit {
+   pub d: f32,
+   pub id: Option<usize>,
+}
+
+impl Ray {
+   /// same ray generation as ``camera_ray()`` in the shader without the lens, ``frac`` is the
+   /// position on the displayed image from the top left (0..1) and ``aspect`` is width / height,
+   /// ``None`` outside the projection
+   pub fn from_camera(settings: &ParthtracerSettings, frac: [f32; 2], aspect: f32) -> Option<Self> {
+      // the display pass flips the image so the top of the image is uv.y = 1
+      let uv = [
+         (frac[0] * 2.0 - 1.0) * aspect,
+         (1.0 - frac[1]) * 2.0 - 1.0,
+      ];
+
+      let mut ro = [0.0; 3];
+      let rd = match settings.projection() {
+         Projection::Perspective => {
+            normalize3([uv[0], uv[1], perspective_focal_length(settings)])
+         }
+         Projection::Orthographic => {
+            ro = [uv[0] * settings.ortho_height * 0.5, uv[1] * settings.ortho_height * 0.5, 0.0];
+            [0.0, 0.0, 1.0]
+         }
+         Projection::Equirectangular => {
+            let lon = uv[0] / aspect * PI;
+            let lat = uv[1] * PI * 0.5;
+            [lat.cos() * lon.sin(), lat.sin(), lat.cos() * lon.cos()]
+         }
+         Projection::Fisheye => {
+            let r = (uv[0] * uv[0] + uv[1] * uv[1]).sqrt();
+            if r > 1.0 { return None; }
+
+            let theta = r * settings.fov.to_radians() * 0.5;
+            let phi = uv[1].atan2(uv[0]);
+            [theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()]
+         }
+      };
+
+      Some(Self {
+         ro: add3(settings.camera_pos, rot3(ro, settings.camera_dir)),
+         rd: rot3(rd, settings.camera_dir),
+      })
+   }
+
+   pub fn at(&self, t: f32) -> [f32; 3] {
+      add3(self.ro, scale3(self.rd, t))
+   }
+}
+
+/// inverse of ``Ray::from_camera``, returns the position of ``p`` on the displayed
+/// image from the top left (0..1) and its depth, along the view axis for perspective and
+/// orthographic and the distance to the camera otherwise, ``None`` if ``p`` can't be seen
+pub fn project(settings: &ParthtracerSettings, p: [f32; 3], aspect: f32) -> Option<([f32; 2], f32)> {
+   let local = inv_rot3(sub3(p, settings.camera_pos), settings.camera_dir);
+
+   let (uv, depth) = match settings.projection() {
+      Projection::Perspective => {
+         if local[2] <= 1e-4 { return None; }
+
+         let f = perspective_focal_length(settings);
+         ([local[0] / local[2] * f, local[1] / local[2] * f], local[2])
+      }
+      Projection::Orthographic => {
+         if local[2] <= 0.0 { return None; }
+
+         let half = settings.ortho_height * 0.5;
+         ([local[0] / half, local[1] / half], local[2])
+      }
+      Projection::Equirectangular => {
+         let d = normalize3(local);
+         let lon = d[0].atan2(d[2]);
+         let lat = d[1].clamp(-1.0, 1.0).asin();
+         ([lon / PI * aspect, lat / (PI * 0.5)], length3(local))
+      }
+      Projection::Fisheye => {
+         let d = normalize3(local);
+         let theta = d[2].clamp(-1.0, 1.0).acos();
+         let r = theta / (settings.fov.to_radians() * 0.5);
+         if r > 1.0 { return None; }
+
+         let phi = d[1].atan2(d[0]);
+         ([r * phi.cos(), r * phi.sin()], length3(local))
+      }
+   };
+
+   let frac = [
+      (uv[0] / aspect + 1.0) * 0.5,
+      1.0 - (uv[1] + 1.0) * 0.5,
+   ];
+
+   Some((frac, depth))
+}
+
+/// z of the view direction that gives ``fov`` degrees vertically
+fn perspective_focal_length(settings: &ParthtracerSettings) -> f32 {
+   1.0 / (settings.fov.to_radians() * 0.5).tan()
+}
+
+pub fn map(scene: &Scene, p_in: [f32; 3]) -> Hit {
+   let mut hit = Hit { d: FP, id: None };
+
+   for (i, obj) in scene.objects.iter().enumerate() {
+      let d = object_distance(obj, p_in);
+      if d < hit.d {
+         hit = Hit { d, id: Some(i) };
+      }
+   }
+
+   hit
+}
+
+pub fn object_distance(obj: &SceneObject, p_in: [f32; 3]) -> f32 {
+   let tr = &obj.transform;
+
+   let p = sub3(p_in, tr.position);
+   let p = rot3(p, tr.rotation);
+   let p = scale3(p, 1.0 / tr.scale);
+
+   shape(obj, p) * tr.scale
+}
+
+pub fn cast_ray(scene: &Scene, ray: &Ray, steps: i32) -> Hit {
+   let mut t = 0.0;
+   for _ in 0..steps {
+      let hit = map(scene, ray.at(t));
+      t += hit.d;
+
+      if hit.d < MHD { return Hit { d: t, id: hit.id }; }
+      if t > FP { break; }
+   }
+
+   Hit { d: t, id: None }
+}
+
+/// returns the object under ``frac`` on the displayed image
+pub fn pick(scene: &Scene, frac: [f32; 2], aspect: f32) -> Hit {
+   let settings = &scene.parthtrace_settings;
+   match Ray::from_camera(settings, frac, aspect) {
+      Some(ray) => cast_ray(scene, &ray, settings.steps_per_ray),
+      None => Hit { d: FP, id: None },
+   }
+}
+
+
+////////////
+// Shapes //
+////////////
+fn shape(obj: &SceneObject, p: [f32; 3]) -> f32 {
+   match obj.shape {
+      ShapeType::Sphere => length3(p) - obj.data[0],
+      ShapeType::Cube => sd_cube(p, obj.data),
+      ShapeType::Octahedron => sd_octahedron_exact(p, obj.data[0]),
+      ShapeType::Mandelbulb => sd_mandelbulb(p, obj.data[0]),
+   }
+}
+
+fn sd_cube(p: [f32; 3], b: [f32; 3]) -> f32 {
+   let q = sub3(abs3(p), b);
+   length3(max3(q, 0.0)) + q[0].max(q[1].max(q[2])).min(0.0)
+}
+
+fn sd_octahedron_exact(p: [f32; 3], s: f32) -> f32 {
+   let p = abs3(p);
+   let m = p[0] + p[1] + p[2] - s;
+
+   let q = if 3.0 * p[0] < m {
+      p
+   } else if 3.0 * p[1] < m {
+      [p[1], p[2], p[0]]
+   } else if 3.0 * p[2] < m {
+      [p[2], p[0], p[1]]
+   } else {
+      return m * 0.57735027;
+   };
+
+   let k = (0.5 * (q[2] - q[1] + s)).clamp(0.0, s);
+   length3([q[0], q[1] - s + k, q[2] - k])
+}
+
+fn sd_mandelbulb(pos: [f32; 3], power: f32) -> f32 {
+   let mut z = pos;
+   let mut dr = 1.0;
+   let mut r = 0.0;
+
+   for _ in 0..100 {
+      r = length3(z);
+      if r > 2.0 { break; }
+
+      let theta = (z[2] / r).acos() * power;
+      let phi = z[1].atan2(z[0]) * power;
+      dr = r.powf(power - 1.0) * power * dr + 1.0;
+      let zr = r.powf(power);
+
+      z = add3(
+         scale3([theta.sin() * phi.cos(), phi.sin() * theta.sin(), theta.cos()], zr),
+         pos,
+      );
+   }
+
+   0.5 * r.ln() * r / dr
+}
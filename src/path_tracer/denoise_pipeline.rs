@@ -0,0 +1,225 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, CommandEncoder, ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, Device, Extent3d, PipelineCompilationOptions, PipelineLayoutDescriptor, ShaderStages, StorageTextureAccess, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureViewDescriptor, TextureViewDimension};
+
+use crate::gpu_profile_section;
+use crate::path_tracer::render_utility::dual_storage_texture_package::read_texture_entry;
+use crate::path_tracer::render_utility::gpu_profiler::GpuProfiler;
+use crate::path_tracer::render_utility::helper_structs::UniformFactory;
+use crate::singletons::settings::DenoiseSettings;
+
+/// one uniform per pass since the step width changes between passes in the same encoder
+pub const MAX_DENOISE_ITERATIONS: u32 = 5;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// à-trous wavelet filter run between the path tracer and the display pass,
+/// edges are kept by weighting with the normal, depth and albedo aovs
+pub struct DenoisePackage {
+   pub pipeline: ComputePipeline,
+   /// ping pong targets, pass ``i`` writes to ``textures[i % 2]``
+   pub textures: [DenoiseTexture; 2],
+   /// the ``textures`` from the size before, kept for dynamic resolution like the path tracer's
+   spare_textures: Option<[DenoiseTexture; 2]>,
+   pub uniforms: Vec<UniformFactory<DenoisePass>>,
+
+   read_layout: BindGroupLayout,
+   write_layout: BindGroupLayout,
+}
+
+impl DenoisePackage {
+   pub fn new(device: &Device, size: Extent3d, aov_layout: &BindGroupLayout) -> Self {
+      // same as the ones in ``StorageTexturePackage`` so the display pass can take either
+      let read_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+         entries: &[read_texture_entry()],
+         label: Some("denoise_bind_group_layout Read Only"),
+      });
+      let write_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+         entries: &[write_texture_entry()],
+         label: Some("denoise_bind_group_layout Write only"),
+      });
+
+      let uniforms = (0..MAX_DENOISE_ITERATIONS)
+          .map(|i| UniformFactory::new(device, &DenoisePass::new(&DenoiseSettings::default(), i)))
+          .collect::<Vec<_>>();
+
+      let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+         label: Some("DenoisePackage pipeline_layout"),
+         bind_group_layouts: &[
+            &read_layout,
+            &write_layout,
+            aov_layout,
+            &uniforms[0].layout,
+         ],
+         push_constant_ranges: &[],
+      });
+
+      let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/denoise_shader.wgsl"));
+
+      let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+         label: Some("DenoisePackage compute_pipeline"),
+         layout: Some(&pipeline_layout),
+         module: &shader,
+         entry_point: "main",
+         compilation_options: PipelineCompilationOptions::default(),
+      });
+
+      let textures = [
+         DenoiseTexture::new(device, size, &read_layout, &write_layout),
+         DenoiseTexture::new(device, size, &read_layout, &write_layout),
+      ];
+
+      Self {
+         pipeline,
+         textures,
+         spare_textures: None,
+         uniforms,
+         read_layout,
+         write_layout,
+      }
+   }
+
+   /// switches the textures if the size changed, to the spare ones if they're that size, and uploads the per pass settings
+   pub fn update(&mut self, device: &Device, queue: &wgpu::Queue, size: Extent3d, settings: &DenoiseSettings) {
+      if self.textures[0].texture.size() != size {
+         let textures = match self.spare_textures.take() {
+            Some(spare) if spare[0].texture.size() == size => spare,
+            _ => [
+               DenoiseTexture::new(device, size, &self.read_layout, &self.write_layout),
+               DenoiseTexture::new(device, size, &self.read_layout, &self.write_layout),
+            ],
+         };
+         self.spare_textures = Some(std::mem::replace(&mut self.textures, textures));
+      }
+
+      for (i, uniform) in self.uniforms.iter().enumerate() {
+         uniform.update_with_data(queue, &DenoisePass::new(settings, i as u32));
+      }
+   }
+
+   /// filters ``input`` and returns the read bind group of the result
+   pub fn render_pass(
+      &self,
+      encoder: &mut CommandEncoder,
+      input: &BindGroup,
+      aovs: &BindGroup,
+      settings: &DenoiseSettings,
+      gpu_profiler: &mut GpuProfiler,
+   ) -> &BindGroup {
+      let iterations = settings.iterations.clamp(1, MAX_DENOISE_ITERATIONS) as usize;
+      let size = self.textures[0].texture.size();
+
+      gpu_profile_section!(gpu_profiler, encoder, "SUB_DENOISE_PASS", {
+         let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("denoise_pipeline"),
+            timestamp_writes: gpu_profiler.compute_pass_writes("SUB_DENOISE_PASS"),
+         });
+
+         compute_pass.set_pipeline(&self.pipeline);
+         compute_pass.set_bind_group(2, aovs, &[]);
+
+         // first pass reads the path tracer output, after that it ping pongs
+         for (i, uniform) in self.uniforms.iter().take(iterations).enumerate() {
+            let read = if i == 0 { input } else { &self.textures[(i - 1) % 2].read_bind_group };
+
+            compute_pass.set_bind_group(0, read, &[]);
+            compute_pass.set_bind_group(1, &self.textures[i % 2].write_bind_group, &[]);
+            compute_pass.set_bind_group(3, &uniform.bind_group, &[]);
+
+            compute_pass.dispatch_workgroups(
+               size.width.div_ceil(WORKGROUP_SIZE),
+               size.height.div_ceil(WORKGROUP_SIZE),
+               1,
+            );
+         }
+      });
+
+      &self.textures[(iterations - 1) % 2].read_bind_group
+   }
+}
+
+
+pub struct DenoiseTexture {
+   pub texture: Texture,
+   pub read_bind_group: BindGroup,
+   pub write_bind_group: BindGroup,
+}
+
+impl DenoiseTexture {
+   fn new(device: &Device, size: Extent3d, read_layout: &BindGroupLayout, write_layout: &BindGroupLayout) -> Self {
+      let texture = device.create_texture(&TextureDescriptor {
+         label: Some("denoise_texture"),
+         size: Extent3d {
+            width: size.width.max(1),
+            height: size.height.max(1),
+            depth_or_array_layers: 1,
+         },
+         mip_level_count: 1,
+         sample_count: 1,
+         dimension: TextureDimension::D2,
+         format: TextureFormat::Rgba32Float,
+         usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC,
+         view_formats: &[],
+      });
+      let view = texture.create_view(&TextureViewDescriptor::default());
+
+      let bind_group = |layout: &BindGroupLayout, label: &str| device.create_bind_group(&BindGroupDescriptor {
+         layout,
+         entries: &[
+            BindGroupEntry {
+               binding: 0,
+               resource: wgpu::BindingResource::TextureView(&view),
+            },
+         ],
+         label: Some(label),
+      });
+
+      let read_bind_group = bind_group(read_layout, "denoise_bind_group Read Only");
+      let write_bind_group = bind_group(write_layout, "denoise_bind_group Write only");
+
+      Self {
+         texture,
+         read_bind_group,
+         write_bind_group,
+      }
+   }
+}
+
+fn write_texture_entry() -> wgpu::BindGroupLayoutEntry {
+   wgpu::BindGroupLayoutEntry {
+      binding: 0,
+      visibility: ShaderStages::FRAGMENT | ShaderStages::COMPUTE,
+      ty: wgpu::BindingType::StorageTexture {
+         access: StorageTextureAccess::WriteOnly,
+         format: TextureFormat::Rgba32Float,
+         view_dimension: TextureViewDimension::D2,
+      },
+      count: None,
+   }
+}
+
+
+#[repr(C)]
+#[derive(Pod, Zeroable, Copy, Clone)]
+pub struct DenoisePass {
+   /// pixels between filter taps, 1, 2, 4, ...
+   step_width: i32,
+   color_phi: f32,
+   normal_phi: f32,
+   depth_phi: f32,
+   albedo_phi: f32,
+}
+
+impl DenoisePass {
+   pub fn new(settings: &DenoiseSettings, iteration: u32) -> Self {
+      // the color tolerance halves every pass so later wide passes only smooth what's left
+      let falloff = 0.5_f32.powi(iteration as i32);
+
+      Self {
+         step_width: 1 << iteration,
+         color_phi: settings.strength * falloff,
+         normal_phi: 64.0,
+         depth_phi: 0.02,
+         albedo_phi: 0.1,
+      }
+   }
+}
@@ -1,33 +1,47 @@
 use bytemuck::{Pod, Zeroable};
+use egui::{DragValue, Ui};
 use egui_wgpu::RenderState;
+use strum::EnumIter;
 use wgpu::{BindGroup, BindGroupLayout, Color, CommandEncoder, IndexFormat, PipelineCompilationOptions, PipelineLayoutDescriptor, RenderPipeline, TextureFormat};
 use crate::app::PROF;
 use crate::gpu_profile_section;
+use crate::path_tracer::render_utility::aov_buffer_package::AovBufferPackage;
 use crate::path_tracer::render_utility::gpu_profiler::GpuProfiler;
 use crate::path_tracer::render_utility::helper_structs::{EguiTexturePackage, f32_to_extent, UniformFactory};
 use crate::path_tracer::render_utility::vertex_library::{SQUARE_INDICES, SQUARE_VERTICES};
 use crate::path_tracer::render_utility::vertex_package::{Vertex, VertexPackage};
+use crate::singletons::scene::ParthtracerSettings;
 use crate::singletons::settings::{ImageSizeSettings, SamplingType};
+use crate::user_interface::ui_modules::enum_combination_box;
 
 pub struct DisplayTexture {
    vertex_package: VertexPackage,
    pub pipeline: RenderPipeline,
    pub texture: EguiTexturePackage,
    pub uniform: UniformFactory<DisplaySettings>,
+
+   /// what gets shown, the beauty pass or one of the aovs
+   pub view_mode: ViewMode,
+   /// distance that maps to black in the depth view
+   pub depth_far: f32,
 }
 
 impl DisplayTexture {
-   pub fn new(render_state: &RenderState, read_bindgroup_layout: &BindGroupLayout, iss: &ImageSizeSettings) -> Self {
+   pub fn new(render_state: &RenderState, read_bindgroup_layout: &BindGroupLayout, aov_bindgroup_layout: &BindGroupLayout, iss: &ImageSizeSettings) -> Self {
       let device = &render_state.device;
       let vertex_package = VertexPackage::new(device, SQUARE_VERTICES, SQUARE_INDICES);
 
-      let uniform = UniformFactory::new(&render_state.device, &DisplaySettings::from_settings(iss));
+      let view_mode = ViewMode::Beauty;
+      let depth_far = 20.0;
+
+      let uniform = UniformFactory::new(&render_state.device, &DisplaySettings::from_settings(iss, None, view_mode, &ParthtracerSettings::default(), 0, depth_far));
 
       let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
          label: Some("Render Pipeline Layout"),
          bind_group_layouts: &[
             read_bindgroup_layout,
             &uniform.layout,
+            aov_bindgroup_layout,
          ],
          push_constant_ranges: &[],
       });
@@ -88,16 +102,41 @@ impl DisplayTexture {
          pipeline,
          texture,
          uniform,
+         view_mode,
+         depth_far,
       }
    }
 
+   /// ``samples`` per pixel dispatched since the last clear, what every pixel has without adaptive sampling
    #[triglyceride::time_event(PROF, "DISPLAY_TEXTURE_UPDATE")]
-   pub fn update(&mut self, render_state: &RenderState, iss: &ImageSizeSettings) {
+   pub fn update(&mut self, render_state: &RenderState, iss: &ImageSizeSettings, selected: Option<usize>, path_set: &ParthtracerSettings, samples: u32) {
       self.texture.update(render_state);
-      self.uniform.update_with_data(&render_state.queue, &DisplaySettings::from_settings(iss))
+
+      let display_settings = DisplaySettings::from_settings(iss, selected, self.view_mode, path_set, samples, self.depth_far);
+      self.uniform.update_with_data(&render_state.queue, &display_settings)
+   }
+
+   pub fn view_mode_ui(&mut self, ui: &mut Ui, aovs: &AovBufferPackage) {
+      ui.horizontal(|ui| {
+         enum_combination_box(ui, &mut self.view_mode, "View");
+
+         if self.view_mode == ViewMode::Depth {
+            ui.add(DragValue::new(&mut self.depth_far).range(0.1..=200.0).speed(0.1).prefix("Far: "));
+         }
+      });
+
+      // the buffers didn't fit on the device
+      let available = match self.view_mode {
+         ViewMode::Beauty => true,
+         ViewMode::Samples => aovs.stats_available,
+         _ => aovs.available,
+      };
+      if !available {
+         ui.label("Debug views are unavailable at this resolution");
+      }
    }
 
-   pub fn render_pass(&self, encoder: &mut CommandEncoder, read_bindgroup: &BindGroup, gpu_profiler: &mut GpuProfiler) {
+   pub fn render_pass(&self, encoder: &mut CommandEncoder, read_bindgroup: &BindGroup, aov_bindgroup: &BindGroup, gpu_profiler: &mut GpuProfiler) {
       gpu_profile_section!(gpu_profiler, encoder, "SUB_DISPLAY_PASS", {
          let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Render Pass"),
@@ -119,13 +158,14 @@ impl DisplayTexture {
             ],
             depth_stencil_attachment: None,
             occlusion_query_set: None,
-            timestamp_writes: None,
+            timestamp_writes: gpu_profiler.render_pass_writes("SUB_DISPLAY_PASS"),
          });
 
          render_pass.set_pipeline(&self.pipeline);
 
          render_pass.set_bind_group(0, read_bindgroup, &[]);
          render_pass.set_bind_group(1, &self.uniform.bind_group, &[]);
+         render_pass.set_bind_group(2, aov_bindgroup, &[]);
 
          render_pass.set_vertex_buffer(0, self.vertex_package.vertex_buffer.slice(..));
          render_pass.set_index_buffer(self.vertex_package.index_buffer.slice(..), IndexFormat::Uint16);
@@ -140,10 +180,18 @@ impl DisplayTexture {
 #[derive(Pod, Zeroable, Copy, Clone)]
 pub struct DisplaySettings {
    sampling_type: u32,
+   /// object id to outline, -1 for none
+   selected_object: i32,
+   view_mode: u32,
+   /// top of the step count heatmap
+   max_steps: u32,
+   depth_far: f32,
+   /// samples a pixel would have without adaptive sampling, the middle of the sample heatmap
+   nominal_samples: u32,
 }
 
 impl DisplaySettings {
-   pub fn from_settings(iss: &ImageSizeSettings) -> Self {
+   pub fn from_settings(iss: &ImageSizeSettings, selected: Option<usize>, view_mode: ViewMode, path_set: &ParthtracerSettings, samples: u32, depth_far: f32) -> Self {
       let sampling_type = match iss.sampling_type {
          SamplingType::Biliniur => 1,
          SamplingType::Linear => 0,
@@ -151,6 +199,23 @@ impl DisplaySettings {
 
       Self {
          sampling_type,
+         selected_object: selected.map_or(-1, |i| i as i32),
+         view_mode: view_mode as u32,
+         max_steps: path_set.steps_per_ray.max(0) as u32,
+         depth_far,
+         nominal_samples: samples.max(1),
       }
    }
+}
+
+/// matches the ``VIEW_`` constants in render_texture_shader.wgsl
+#[derive(Copy, Clone, EnumIter, Debug, PartialEq)]
+pub enum ViewMode {
+   Beauty = 0,
+   Normal = 1,
+   Depth = 2,
+   Albedo = 3,
+   Steps = 4,
+   ObjectId = 5,
+   Samples = 6,
 }
\ No newline at end of file
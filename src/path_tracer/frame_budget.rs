@@ -0,0 +1,79 @@
+use instant::Instant;
+
+use crate::singletons::settings::FrameBudgetSettings;
+
+/// seconds between adjustments, single frame timings are too noisy to react to
+const ADJUST_INTERVAL: f64 = 0.25;
+/// weight of a new timing in the running average
+const SMOOTHING: f64 = 0.2;
+/// upper bound for tiled render dispatches
+const MAX_DISPATCHES_PER_FRAME: u32 = 64;
+
+/// keeps the frame time near ``FrameBudgetSettings::target_ms`` by changing how much work is done
+pub struct FrameBudget {
+   /// smoothed ms, none until the gpu has reported a time
+   pass_ms: Option<f64>,
+   frame_ms: f64,
+   last_adjust: Instant,
+}
+
+impl FrameBudget {
+   pub fn new() -> Self {
+      Self {
+         pass_ms: None,
+         frame_ms: 0.0,
+         last_adjust: Instant::now(),
+      }
+   }
+
+   /// ``pass_ms`` is the path trace pass from the gpu profiler, none without timestamp queries
+   pub fn record(&mut self, pass_ms: Option<f64>, frame_ms: f64) {
+      let smooth = |old: f64, new: f64| if old > 0.0 { old + (new - old) * SMOOTHING } else { new };
+
+      self.pass_ms = pass_ms.filter(|ms| *ms > 0.0).map(|ms| smooth(self.pass_ms.unwrap_or(0.0), ms));
+      self.frame_ms = smooth(self.frame_ms, frame_ms);
+   }
+
+   /// true once per ``ADJUST_INTERVAL``
+   pub fn should_adjust(&mut self) -> bool {
+      if self.last_adjust.elapsed().as_secs_f64() < ADJUST_INTERVAL { return false; }
+      self.last_adjust = Instant::now();
+      true
+   }
+
+   /// new samples per frame for the viewport
+   pub fn tune_samples(&self, settings: &FrameBudgetSettings, current: i32) -> i32 {
+      let target = settings.target_ms as f64;
+
+      // with gpu timings only the pass is scaled, everything else in the frame is left as overhead
+      let (cost, budget) = match self.pass_ms {
+         Some(pass_ms) => (pass_ms, (target - (self.frame_ms - pass_ms).max(0.0)).max(1.0)),
+         None => (self.frame_ms, target),
+      };
+
+      tune(current.max(1) as u32, cost, budget, settings.max_samples_per_frame.max(1) as u32) as i32
+   }
+
+   /// new dispatches per frame for a running tiled render
+   pub fn tune_dispatches(&self, settings: &FrameBudgetSettings, current: u32) -> u32 {
+      tune(current.max(1), self.frame_ms, settings.target_ms as f64, MAX_DISPATCHES_PER_FRAME)
+   }
+}
+
+/// assumes the cost scales linearly with ``current``, grows slowly and drops straight away
+fn tune(current: u32, cost_ms: f64, budget_ms: f64, max: u32) -> u32 {
+   if cost_ms <= 0.0 { return current; }
+
+   // close enough, stops it flickering between two values
+   let ratio = budget_ms / cost_ms;
+   if (0.9..=1.1).contains(&ratio) { return current.clamp(1, max); }
+
+   let ideal = (budget_ms / (cost_ms / current as f64)).floor() as u32;
+   let next = if ideal > current {
+      ideal.min(current + (current / 4).max(1))
+   } else {
+      ideal
+   };
+
+   next.clamp(1, max)
+}
@@ -4,104 +4,251 @@ use std::iter;
 
 use eframe::CreationContext;
 use eframe::emath::{Rect, Vec2};
-use egui::{Button, Image, Pos2, Response, Sense, Ui};
+use egui::{Button, Image, Pos2, Response, SelectableLabel, Sense, Ui};
 use egui::load::SizedTexture;
 use egui_wgpu::RenderState;
+use instant::Instant;
 use triglyceride::time_event_mac;
 use wgpu::{CommandEncoderDescriptor, Extent3d};
 
-use crate::{get, get_mut_ref, gpu_profile_section};
+use crate::gpu_profile_section;
+use crate::path_tracer::benchmark::Benchmark;
+use crate::path_tracer::cpu_raymarcher;
+use crate::path_tracer::denoise_pipeline::DenoisePackage;
+use crate::path_tracer::frame_budget::FrameBudget;
 use crate::path_tracer::display_texture_pipeline::DisplayTexture;
 use crate::path_tracer::path_tracer_package::PathTracerPackage;
-use crate::path_tracer::render_utility::gpu_profiler::GpuProfiler;
-use crate::singletons::settings::SETTINGS;
-use crate::singletons::time_package::TIME;
+use crate::path_tracer::render_queue::RenderQueue;
+use crate::path_tracer::tiled_renderer::TiledRenderer;
+use crate::path_tracer::render_utility::gpu_profiler::{GpuProfiler, TimestampMode};
+use crate::singletons::app_context::AppContext;
+use crate::singletons::scene::{CameraView, ParthtracerSettings, Scene, SceneObjectsUniform};
+use crate::singletons::settings::{DenoiseSettings, Settings};
+use crate::user_interface::gizmo;
+use crate::user_interface::gizmo::Gizmo;
+
+const CAMERA_TRANSITION_SECONDS: f32 = 0.75;
+
+/// samples per pixel the viewport has actually dispatched since the last clear, counted
+/// as they're rendered since the samples per frame can change between frames
+#[derive(Default)]
+struct SampleCounter {
+   accumulated: u32,
+   /// samples per frame of the uniform waiting to be rendered
+   pending: u32,
+}
+
+impl SampleCounter {
+   fn uploaded(&mut self, path_set: &ParthtracerSettings) {
+      // same test the shader uses to start over
+      if path_set.frame == path_set.last_clear_frame {
+         self.accumulated = 0;
+      }
+      // same as the shader
+      self.pending = path_set.samples_per_frame.max(1) as u32;
+   }
+
+   fn rendered(&mut self) {
+      self.accumulated += self.pending;
+   }
+}
 
 pub struct PathTracerRenderer {
    path_tracer_package: PathTracerPackage,
    display_texture: DisplayTexture,
+   denoise_package: DenoisePackage,
+   tiled_renderer: TiledRenderer,
+   render_queue: RenderQueue,
+   frame_budget: FrameBudget,
 
    queue_pipeline_remake: bool,
+   gizmo: Gizmo,
+   pick_focus: bool,
+   camera_transition: Option<CameraTransition>,
+   /// everything that changes the image last frame, accumulation restarts when it changes
+   accumulation_key: Vec<u8>,
+   /// when ``accumulation_key`` last changed, drives dynamic resolution
+   last_change: Instant,
+   samples: SampleCounter,
 
    pub do_gpu_profiling: bool,
    pub gpu_profiler: GpuProfiler,
+   pub benchmark: Benchmark,
 }
 
 impl PathTracerRenderer {
    /// # Panics
-   pub fn new(cc: &CreationContext) -> Self {
+   pub fn new(cc: &CreationContext, settings: &Settings) -> Self {
       let render_state = cc.wgpu_render_state.as_ref().expect("Couldn't unwrap render state");
 
-      get_mut_ref!(SETTINGS, settings);
-
-      let path_tracer_package = PathTracerPackage::new(render_state, &settings.current_scene.parthtrace_settings);
-      let display_texture =
-          DisplayTexture::new(render_state, path_tracer_package.storage_textures.read_layout(), &settings.image_size_settings);
+      let path_tracer_package = PathTracerPackage::new(render_state, &settings.current_scene);
+      let display_texture = DisplayTexture::new(
+         render_state,
+         path_tracer_package.storage_textures.read_layout(),
+         &path_tracer_package.storage_textures.aovs.read_bind_group_layout,
+         &settings.image_size_settings,
+      );
+      let denoise_package = DenoisePackage::new(
+         &render_state.device,
+         path_tracer_package.storage_textures.size,
+         &path_tracer_package.storage_textures.aovs.read_bind_group_layout,
+      );
 
 
-      let gpu_profiler = GpuProfiler::new(&render_state.device, 6);
+      let gpu_profiler = GpuProfiler::new(&render_state.device, 8);
 
       let do_gpu_profiling = cfg!(not(target_arch = "wasm32"));
 
       Self {
          path_tracer_package,
          display_texture,
+         denoise_package,
+         tiled_renderer: TiledRenderer::new(render_state),
+         render_queue: RenderQueue::new(render_state),
+         frame_budget: FrameBudget::new(),
 
          queue_pipeline_remake: false,
+         gizmo: Gizmo::new(),
+         pick_focus: false,
+         camera_transition: None,
+         accumulation_key: vec![],
+         last_change: Instant::now(),
+         samples: SampleCounter::default(),
 
          do_gpu_profiling,
 
          gpu_profiler,
+         benchmark: Benchmark::new(),
       }
    }
 
    #[triglyceride::time_event(PROF, "PATHTRACER_UPDATE")]
-   pub fn update(&mut self, render_state: &RenderState) {
-      get_mut_ref!(SETTINGS, settings);
-
-      self.render_pass(render_state);
+   pub fn update(&mut self, render_state: &RenderState, selected: Option<usize>, context: &mut AppContext) {
+      let _s = context.trace.scope("PATHTRACER_UPDATE");
+      let settings = &mut context.settings;
 
+      {
+         let _s = context.trace.scope("RENDERPASS");
+         self.render_pass(render_state, &settings.denoise_settings);
+      }
+      self.samples.rendered();
 
-      self.display_texture.update(render_state, &settings.image_size_settings);
+      self.display_texture.update(render_state, &settings.image_size_settings, selected, &settings.current_scene.parthtrace_settings, self.samples.accumulated);
 
       if self.queue_pipeline_remake {
          self.path_tracer_package.remake_pipeline(&render_state.device);
+         self.tiled_renderer.remake_pipeline(&render_state.device);
+         self.render_queue.remake_pipeline(&render_state.device);
          self.queue_pipeline_remake = false;
+         self.accumulation_key.clear();
       }
 
       time_event_mac!(PROF, "UPDATE_GPU_PROFILER", {
          #[cfg(not(target_arch = "wasm32"))]
-         { self.gpu_profiler.active = self.do_gpu_profiling; }
+         { self.gpu_profiler.active = (self.do_gpu_profiling || self.benchmark.is_running()) && self.gpu_profiler.mode != TimestampMode::Unsupported; }
 
          #[cfg(target_arch = "wasm32")]
          { self.gpu_profiler.active = false; self.do_gpu_profiling = false; }
 
+         self.gpu_profiler.capture = context.trace.is_recording() || self.benchmark.is_running();
          self.gpu_profiler.update(&render_state.queue, &render_state.device);
+
+         let captured = self.gpu_profiler.take_captured();
+         self.benchmark.gpu_frames(&captured);
+         context.trace.gpu_frames(captured);
        });
 
+      // takes over the scene and camera while it runs
+      self.benchmark.update(context, self.gpu_profiler.active);
+      if self.benchmark.is_running() { self.camera_transition = None; }
+      let settings = &mut context.settings;
+
       // update scene
       time_event_mac!(PROF, "UPDATE_SCENE", {
          let path_set = &mut settings.current_scene.parthtrace_settings;
-         path_set.time = get!(TIME).start_time.elapsed().as_secs_f32();
+         path_set.time = context.time.start_time.elapsed().as_secs_f32();
          path_set.frame += 1;
 
-         self.path_tracer_package.uniform.update_with_data(&render_state.queue, path_set);
+         if let Some(transition) = &self.camera_transition {
+            let t = (transition.start.elapsed().as_secs_f32() / CAMERA_TRANSITION_SECONDS).min(1.0);
+            let eased = t * t * (3.0 - 2.0 * t);
+            transition.from.lerp(&transition.to, eased).apply(path_set);
+
+            if t >= 1.0 { self.camera_transition = None; }
+         }
+
+         self.apply_frame_budget(settings, context.time.delta_time);
+
+         // any change counts as input for dynamic resolution
+         let key = accumulation_key(settings);
+         let changed = key != self.accumulation_key;
+         if changed {
+            self.accumulation_key = key;
+            self.last_change = Instant::now();
+         }
 
+         // anything bigger than the device allows has to go through the tiled renderer
          let iss = settings.image_size_settings;
-         self.path_tracer_package.storage_textures.size.width = iss.width;
-         self.path_tracer_package.storage_textures.size.height = iss.height;
+         let max_size = render_state.device.limits().max_texture_dimension_2d;
+         let fit = (max_size as f32 / iss.width.max(iss.height) as f32).min(1.0);
+
+         let drs = settings.dynamic_resolution_settings;
+         let interacting = drs.enabled && self.last_change.elapsed().as_secs_f32() < drs.settle_time;
+         let scale = if interacting { fit * drs.scale } else { fit };
+
+         let size = Extent3d {
+            width: ((iss.width as f32 * scale) as u32).max(1),
+            height: ((iss.height as f32 * scale) as u32).max(1),
+            depth_or_array_layers: 1,
+         };
+
+         // the old samples are the wrong resolution so start over
+         if changed || size != self.path_tracer_package.storage_textures.size {
+            settings.current_scene.parthtrace_settings.last_clear_frame = settings.current_scene.parthtrace_settings.frame;
+         }
+
+         let path_set = &settings.current_scene.parthtrace_settings;
+         self.path_tracer_package.uniform.update_with_data(&render_state.queue, path_set);
+         self.samples.uploaded(path_set);
+         self.path_tracer_package.update_objects(render_state, &settings.current_scene);
+
+         self.path_tracer_package.storage_textures.size = size;
          self.path_tracer_package.storage_textures.update(&render_state.device);
+
+         self.denoise_package.update(&render_state.device, &render_state.queue, size, &settings.denoise_settings);
       });
 
+      self.tiled_renderer.update(render_state, &settings.tiled_render_settings, &settings.current_scene);
+      self.render_queue.update(render_state);
+
+
+   }
 
+   /// false once ``converged_samples`` samples per pixel have been dispatched since the last clear and nothing
+   /// else is running, the app stops repainting then until there's input, with adaptive sampling pixels
+   /// under the noise threshold take fewer so only the converged ones can be short
+   pub fn wants_repaint(&self, settings: &Settings) -> bool {
+      let pacing = settings.frame_pacing_settings;
+      if !pacing.idle_when_converged { return true; }
+
+      let busy = self.tiled_renderer.is_busy()
+          || self.render_queue.is_busy()
+          || self.benchmark.is_running()
+          || self.camera_transition.is_some()
+          || self.queue_pipeline_remake;
+      // dynamic resolution still has to go back to full size
+      let settling = settings.dynamic_resolution_settings.enabled
+          && self.last_change.elapsed().as_secs_f32() < settings.dynamic_resolution_settings.settle_time;
+
+      busy || settling || self.samples.accumulated < pacing.converged_samples
    }
 
-   pub fn display(&mut self, ui: &mut Ui) {
+   /// ``selection`` is the selected graph node, set when an object is clicked on
+   pub fn display(&mut self, ui: &mut Ui, selection: &mut Option<usize>, settings: &mut Settings) {
       // init
       let max = to_extent(ui.available_size());
       let mut ms = max;
 
-      get_mut_ref!(SETTINGS, settings);
       let iss = settings.image_size_settings;
 
       // calc texture size
@@ -140,18 +287,163 @@ impl PathTracerRenderer {
          // refresh button
          let rect = Rect::from_center_size(response.rect.min + Pos2::new(10.0, 10.0).to_vec2(), Vec2::new(20.0, 20.0));
          if ui.put(rect, Button::new("🔄")).clicked() {
-            self.queue_pipeline_remake = true;
+            self.recompile_shader();
          }
 
+         self.camera_menu(ui, response.rect, &mut settings.current_scene);
+         self.view_mode_menu(ui, response.rect);
+         denoise_toggle(ui, response.rect, &mut settings.denoise_settings);
+
+         // gizmo
+         let gizmo_active = self.gizmo(ui, &response, *selection, settings);
+
          // delegate input
-         self.handle_input(ui, &response);
+         if !gizmo_active {
+            self.handle_input(ui, &response, selection, settings);
+         }
+      });
+   }
+
+   /// retunes the amount of work per frame towards the target frame time
+   fn apply_frame_budget(&mut self, settings: &mut Settings, delta_time: f64) {
+      let frame_ms = delta_time * 1000.0;
+      self.frame_budget.record(self.gpu_profiler.latest("SUB_PATHTRACE_PASS"), frame_ms);
+
+      let budget = settings.frame_budget_settings;
+      if !budget.enabled || !self.frame_budget.should_adjust() { return; }
+
+      // a tiled render is the heavier job, leave the viewport alone while it runs
+      let dispatches = self.tiled_renderer.dispatches_per_frame()
+          .or_else(|| self.render_queue.dispatches_per_frame());
+      if let Some(dispatches) = dispatches {
+         *dispatches = self.frame_budget.tune_dispatches(&budget, *dispatches);
+      } else {
+         let path_set = &mut settings.current_scene.parthtrace_settings;
+         path_set.samples_per_frame = self.frame_budget.tune_samples(&budget, path_set.samples_per_frame);
+      }
+   }
+
+   pub fn tiled_render_ui(&mut self, ui: &mut Ui, settings: &mut Settings) {
+      self.tiled_renderer.ui(ui, &mut settings.tiled_render_settings);
+   }
+
+   pub fn render_queue_ui(&mut self, ui: &mut Ui, current_scene: &Scene, saved_scenes: &[Scene]) {
+      self.render_queue.ui(ui, current_scene, saved_scenes);
+   }
+
+   /// starts a smooth transition to a scene camera
+   pub fn switch_camera(&mut self, index: usize, scene: &mut Scene) {
+      if let Some(camera) = scene.cameras.get(index) {
+         self.camera_transition = Some(CameraTransition {
+            from: CameraView::from_settings(&scene.parthtrace_settings),
+            to: camera.view,
+            start: Instant::now(),
+         });
+         scene.active_camera = Some(index);
+      }
+   }
+
+   /// eases back to the default position and direction, the lens is kept
+   pub fn reset_camera(&mut self, scene: &mut Scene) {
+      let from = CameraView::from_settings(&scene.parthtrace_settings);
+      let default = ParthtracerSettings::default();
+
+      self.camera_transition = Some(CameraTransition {
+         from,
+         to: CameraView { position: default.camera_pos, rotation: default.camera_dir, ..from },
+         start: Instant::now(),
+      });
+      scene.active_camera = None;
+   }
+
+   pub fn recompile_shader(&mut self) {
+      self.queue_pipeline_remake = true;
+   }
+
+   /// camera switching overlay in the top right of the viewport
+   fn camera_menu(&mut self, ui: &mut Ui, rect: Rect, scene: &mut Scene) {
+      let mut switch_to = None;
+
+      let menu_rect = Rect::from_min_size(Pos2::new(rect.max.x - 30.0, rect.min.y), Vec2::new(30.0, 20.0));
+      ui.put(menu_rect, |ui: &mut Ui| {
+         ui.menu_button("📷", |ui| {
+            for (i, camera) in scene.cameras.iter().enumerate() {
+               if ui.selectable_label(scene.active_camera == Some(i), &camera.name).clicked() {
+                  switch_to = Some(i);
+                  ui.close_menu();
+               }
+            }
+
+            ui.separator();
+            if ui.button("Save current view").clicked() {
+               scene.save_current_view();
+               ui.close_menu();
+            }
+         }).response
       });
+
+      if let Some(i) = switch_to {
+         self.switch_camera(i, scene);
+      }
+   }
+
+   /// aov view selector overlay in the bottom left of the viewport
+   fn view_mode_menu(&mut self, ui: &mut Ui, rect: Rect) {
+      let aovs = &self.path_tracer_package.storage_textures.aovs;
+
+      let menu_rect = Rect::from_min_size(Pos2::new(rect.min.x, rect.max.y - 20.0), Vec2::new(30.0, 20.0));
+      ui.put(menu_rect, |ui: &mut Ui| {
+         ui.menu_button("👁", |ui| {
+            self.display_texture.view_mode_ui(ui, aovs);
+         }).response
+      }).on_hover_text(format!("{:?}", self.display_texture.view_mode));
    }
 
-   fn handle_input(&mut self, _ui: &mut Ui, _response: &Response) {}
+   /// draws the transform gizmo for the selected object, returns true if it is using the pointer
+   fn gizmo(&mut self, ui: &mut Ui, response: &Response, selection: Option<usize>, settings: &mut Settings) -> bool {
+      if gizmo::toolbar(ui, response.rect, &mut settings.gizmo_settings) {
+         self.gizmo.cancel();
+      }
+
+      // focus picking, sits after the gizmo toolbar
+      let focus_rect = Rect::from_min_size(response.rect.min + Vec2::new(128.0, 0.0), Vec2::new(20.0, 20.0));
+      if ui.put(focus_rect, SelectableLabel::new(self.pick_focus, "🎯")).on_hover_text("Pick focus").clicked() {
+         self.pick_focus = !self.pick_focus;
+      }
+      if self.pick_focus {
+         self.gizmo.cancel();
+         return false;
+      }
+
+      let iss = settings.image_size_settings;
+      let aspect = iss.width as f32 / iss.height as f32;
+      let camera = settings.current_scene.parthtrace_settings;
+      let gs = settings.gizmo_settings;
+
+      match selection.and_then(|i| settings.current_scene.objects.get_mut(i)) {
+         Some(object) => self.gizmo.ui(ui, response, &camera, aspect, &gs, &mut object.transform),
+         None => {
+            self.gizmo.cancel();
+            false
+         }
+      }
+   }
+
+   fn handle_input(&mut self, _ui: &mut Ui, response: &Response, selection: &mut Option<usize>, settings: &mut Settings) {
+      if response.clicked() {
+         if let Some(pos) = response.interact_pointer_pos() {
+            if self.pick_focus {
+               self.pick_focus = false;
+               focus_on(settings, response.rect, pos);
+            } else {
+               *selection = pick(settings, response.rect, pos);
+            }
+         }
+      }
+   }
 
    #[triglyceride::time_event(PROF, "RENDERPASS")]
-   fn render_pass(&mut self, render_state: &RenderState) {
+   fn render_pass(&mut self, render_state: &RenderState, denoise_settings: &DenoiseSettings) {
       let mut encoder = render_state.device.create_command_encoder(&CommandEncoderDescriptor {
          label: Some("Render Encoder"),
       });
@@ -160,8 +452,18 @@ impl PathTracerRenderer {
 
          self.path_tracer_package.render_pass(&mut encoder, &mut self.gpu_profiler);
 
+         let storage_textures = &self.path_tracer_package.storage_textures;
+         let raw = &storage_textures.textures.item_one().read_bind_group;
+
+         let shown = if denoise_settings.enabled {
+            self.denoise_package.render_pass(&mut encoder, raw, &storage_textures.aovs.read_bind_group, denoise_settings, &mut self.gpu_profiler)
+         } else {
+            raw
+         };
+
          self.display_texture.render_pass(&mut encoder,
-            &self.path_tracer_package.storage_textures.textures.item_one().read_bind_group,
+            shown,
+            &storage_textures.aovs.read_bind_group,
             &mut self.gpu_profiler
          );
 
@@ -173,10 +475,86 @@ impl PathTracerRenderer {
    }
 }
 
+/// raw / denoised comparison toggle, sits after the focus picker
+fn denoise_toggle(ui: &mut Ui, rect: Rect, denoise_settings: &mut DenoiseSettings) {
+   let enabled = &mut denoise_settings.enabled;
+
+   let toggle_rect = Rect::from_min_size(rect.min + Vec2::new(150.0, 0.0), Vec2::new(20.0, 20.0));
+   let hover = if *enabled { "Showing denoised" } else { "Showing raw" };
+   if ui.put(toggle_rect, SelectableLabel::new(*enabled, "✨")).on_hover_text(hover).clicked() {
+      *enabled = !*enabled;
+   }
+}
+
+/// raymarches the clicked pixel on the cpu and returns the hit object
+#[triglyceride::time_event(PROF, "PICK_OBJECT")]
+fn pick(settings: &Settings, rect: Rect, pos: Pos2) -> Option<usize> {
+   let iss = settings.image_size_settings;
+   let aspect = iss.width as f32 / iss.height as f32;
+
+   cpu_raymarcher::pick(&settings.current_scene, viewport_frac(rect, pos), aspect).id
+}
+
+/// sets the focal distance to the surface under ``pos``, misses are ignored
+#[triglyceride::time_event(PROF, "PICK_FOCUS")]
+fn focus_on(settings: &mut Settings, rect: Rect, pos: Pos2) {
+   let iss = settings.image_size_settings;
+   let aspect = iss.width as f32 / iss.height as f32;
+   let scene = &mut settings.current_scene;
+
+   let ray = match cpu_raymarcher::Ray::from_camera(&scene.parthtrace_settings, viewport_frac(rect, pos), aspect) {
+      Some(ray) => ray,
+      None => return,
+   };
+   let hit = cpu_raymarcher::cast_ray(scene, &ray, scene.parthtrace_settings.steps_per_ray);
+   if hit.id.is_none() { return; }
+
+   // the focal plane is perpendicular to the view axis so use the depth not the ray length
+   if let Some((_, depth)) = cpu_raymarcher::project(&scene.parthtrace_settings, ray.at(hit.d), aspect) {
+      scene.parthtrace_settings.focal_distance = depth;
+   }
+}
+
+/// position of ``pos`` on the viewport image from the top left (0..1)
+fn viewport_frac(rect: Rect, pos: Pos2) -> [f32; 2] {
+   [
+      (pos.x - rect.min.x) / rect.width(),
+      (pos.y - rect.min.y) / rect.height(),
+   ]
+}
+
+/// bytes of everything that affects the rendered image, time and frame counters are left out
+fn accumulation_key(settings: &Settings) -> Vec<u8> {
+   let mut path_set = settings.current_scene.parthtrace_settings;
+   path_set.time = 0.0;
+   path_set.frame = 0;
+   path_set.last_clear_frame = 0;
+   // pixels are blended by their own sample count so more or less per frame is fine
+   path_set.samples_per_frame = 0;
+   // the accumulated mean stays valid when these change, it just gets sampled differently
+   path_set.adaptive_sampling = 0;
+   path_set.noise_threshold = 0.0;
+   path_set.min_adaptive_samples = 0;
+
+   let iss = settings.image_size_settings;
+
+   let mut key = bytemuck::bytes_of(&path_set).to_vec();
+   key.extend_from_slice(bytemuck::bytes_of(&SceneObjectsUniform::from_scene(&settings.current_scene)));
+   key.extend_from_slice(bytemuck::bytes_of(&[iss.width, iss.height]));
+   key
+}
+
 pub fn to_extent(vec2: Vec2) -> Extent3d {
    Extent3d {
       width: vec2.x as u32,
       height: vec2.y as u32,
       depth_or_array_layers: 1,
    }
-}
\ No newline at end of file
+}
+
+
+struct CameraTransition {
+   from: CameraView,
+   to: CameraView,
+   start: Instant,
+}
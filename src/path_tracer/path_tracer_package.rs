@@ -11,18 +11,19 @@ use crate::gpu_profile_section;
 use crate::path_tracer::render_utility::dual_storage_texture_package::DualStorageTexturePackage;
 use crate::path_tracer::render_utility::gpu_profiler::GpuProfiler;
 use crate::path_tracer::render_utility::helper_structs::UniformFactory;
-use crate::singletons::scene::ParthtracerSettings;
+use crate::singletons::scene::{ParthtracerSettings, Scene, SceneObjectsUniform};
 
 pub struct PathTracerPackage {
    pub pipeline_layout: PipelineLayout,
    pub compute_pipeline: ComputePipeline,
    pub storage_textures: DualStorageTexturePackage,
    pub uniform: UniformFactory<ParthtracerSettings>,
+   pub objects_uniform: UniformFactory<SceneObjectsUniform>,
 }
 
 impl PathTracerPackage {
    /// # Panics
-   pub fn new(render_state: &RenderState, parthtracer_settings: &ParthtracerSettings) -> Self {
+   pub fn new(render_state: &RenderState, scene: &Scene) -> Self {
       let device = &render_state.device;
 
       let storage_textures = DualStorageTexturePackage::new(device);
@@ -30,7 +31,8 @@ impl PathTracerPackage {
       // todo placeholder
       let shader_module = load_shader(device, &String::new()).expect("Failed to load shader");
 
-      let uniform = UniformFactory::new(device, parthtracer_settings);
+      let uniform = UniformFactory::new(device, &scene.parthtrace_settings);
+      let objects_uniform = UniformFactory::new(device, &SceneObjectsUniform::from_scene(scene));
 
       let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
          label: Some("PathTracerPackage pipeline_layout"),
@@ -38,6 +40,7 @@ impl PathTracerPackage {
             storage_textures.read_layout(),
             storage_textures.write_layout(),
             &uniform.layout,
+            &objects_uniform.layout,
          ],
          push_constant_ranges: &[],
       });
@@ -55,6 +58,7 @@ impl PathTracerPackage {
          compute_pipeline,
          storage_textures,
          uniform,
+         objects_uniform,
       }
    }
 
@@ -62,11 +66,15 @@ impl PathTracerPackage {
       self.uniform.update_with_data(&render_state.queue, &settings);
    }
 
+   pub fn update_objects(&mut self, render_state: &RenderState, scene: &Scene) {
+      self.objects_uniform.update_with_data(&render_state.queue, &SceneObjectsUniform::from_scene(scene));
+   }
+
    pub fn render_pass(&mut self, encoder: &mut CommandEncoder, gpu_profiler: &mut GpuProfiler) {
       gpu_profile_section!(gpu_profiler, encoder, "SUB_PATHTRACE_PASS", {
          let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
             label: Some("path_tracer_pipeline"),
-            timestamp_writes: None,
+            timestamp_writes: gpu_profiler.compute_pass_writes("SUB_PATHTRACE_PASS"),
          });
 
          compute_pass.set_pipeline(&self.compute_pipeline);
@@ -75,6 +83,7 @@ impl PathTracerPackage {
          compute_pass.set_bind_group(0, &self.storage_textures.textures.item_one().read_bind_group, &[]);
          compute_pass.set_bind_group(1, &self.storage_textures.textures.item_two().write_bind_group, &[]);
          compute_pass.set_bind_group(2, &self.uniform.bind_group, &[]);
+         compute_pass.set_bind_group(3, &self.objects_uniform.bind_group, &[]);
 
          let size = self.storage_textures.size;
          let wg = 16;
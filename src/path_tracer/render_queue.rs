@@ -0,0 +1,393 @@
+use egui::{ComboBox, ProgressBar, ScrollArea, TextEdit, Ui};
+use egui_wgpu::RenderState;
+use wgpu::Device;
+use instant::Instant;
+
+use crate::app::PROF;
+use crate::path_tracer::tiled_renderer::{TiledEvent, TiledRenderer};
+use crate::singletons::scene::Scene;
+use crate::singletons::settings::TiledRenderSettings;
+
+/// lines kept in the log before the oldest are dropped
+const MAX_LOG_LINES: usize = 200;
+
+/// renders queued jobs one after another through its own ``TiledRenderer``,
+/// so the viewport and the tiled render panel keep working while it runs
+pub struct RenderQueue {
+   renderer: TiledRenderer,
+   jobs: Vec<QueuedRender>,
+   /// index into ``jobs`` of the one being rendered
+   running: Option<usize>,
+   paused: bool,
+
+   /// settings for the next job added
+   draft: TiledRenderSettings,
+   /// index into ``Settings::saved_scenes``, none renders the current scene
+   draft_scene: Option<usize>,
+   /// none renders the view the scene is at when the job is added
+   draft_camera: Option<usize>,
+
+   log: Vec<String>,
+   created: Instant,
+}
+
+struct QueuedRender {
+   /// camera name, or "Current view", after the saved scene's name if it's from one
+   name: String,
+   /// snapshot taken when queued with the camera already applied, later edits don't leak in
+   scene: Scene,
+   settings: TiledRenderSettings,
+   status: QueueStatus,
+}
+
+#[derive(Clone, PartialEq)]
+enum QueueStatus {
+   Pending,
+   Running,
+   Done { seconds: f32 },
+   Failed(String),
+   Cancelled,
+}
+
+impl RenderQueue {
+   pub fn new(render_state: &RenderState) -> Self {
+      Self {
+         renderer: TiledRenderer::new(render_state),
+         jobs: vec![],
+         running: None,
+         paused: false,
+         draft: TiledRenderSettings {
+            width: 1920,
+            height: 1080,
+            ..TiledRenderSettings::default()
+         },
+         draft_scene: None,
+         draft_camera: None,
+         log: vec![],
+         created: Instant::now(),
+      }
+   }
+
+   /// has work to do next frame
+   pub fn is_busy(&self) -> bool {
+      !self.paused && (self.running.is_some() || self.jobs.iter().any(|job| job.status == QueueStatus::Pending))
+   }
+
+   /// dispatches per frame of the running job
+   pub fn dispatches_per_frame(&mut self) -> Option<&mut u32> {
+      self.renderer.dispatches_per_frame()
+   }
+
+   pub fn remake_pipeline(&mut self, device: &Device) {
+      self.renderer.remake_pipeline(device);
+   }
+
+   /// queues a job rendering ``scene`` from ``camera``, ``scene_name`` is set for saved scenes
+   pub fn push(&mut self, scene: &Scene, scene_name: Option<String>, camera: Option<usize>, settings: TiledRenderSettings) {
+      let mut scene = scene.clone();
+
+      let camera_name = match camera.and_then(|i| scene.cameras.get(i)) {
+         Some(camera) => {
+            let name = camera.name.clone();
+            camera.view.apply(&mut scene.parthtrace_settings);
+            name
+         }
+         None => "Current view".to_string(),
+      };
+      let name = match scene_name {
+         Some(scene_name) => format!("{scene_name}, {camera_name}"),
+         None => camera_name,
+      };
+
+      self.log(format!("Queued {name} -> {}", settings.output_path));
+      self.jobs.push(QueuedRender {
+         name,
+         scene,
+         settings,
+         status: QueueStatus::Pending,
+      });
+   }
+
+   #[triglyceride::time_event(PROF, "RENDER_QUEUE_UPDATE")]
+   pub fn update(&mut self, render_state: &RenderState) {
+      if self.paused { return; }
+
+      if self.running.is_none() {
+         self.start_next(render_state);
+      }
+      let Some(index) = self.running else { return; };
+
+      let Some(event) = self.renderer.render(render_state) else { return; };
+      self.running = None;
+
+      let job = &mut self.jobs[index];
+      let line = match event {
+         TiledEvent::Finished { path, seconds } => {
+            job.status = QueueStatus::Done { seconds };
+            format!("Finished {} in {seconds:.1}s, saved {path}", job.name)
+         }
+         TiledEvent::Failed(e) => {
+            let line = format!("{} failed: {e}", job.name);
+            job.status = QueueStatus::Failed(e);
+            line
+         }
+      };
+      self.log(line);
+   }
+
+   /// starts the first pending job, ones that can't start are marked failed and skipped
+   fn start_next(&mut self, render_state: &RenderState) {
+      while let Some(index) = self.jobs.iter().position(|job| job.status == QueueStatus::Pending) {
+         let job = &mut self.jobs[index];
+
+         match self.renderer.start(render_state, &job.settings, &job.scene) {
+            Ok(()) => {
+               job.status = QueueStatus::Running;
+               self.running = Some(index);
+
+               let line = format!(
+                  "Started {} at {}x{}, {} samples",
+                  job.name, job.settings.width, job.settings.height, job.settings.samples_per_pixel,
+               );
+               self.log(line);
+               return;
+            }
+            Err(e) => {
+               let line = format!("Couldn't start {}: {e}", job.name);
+               job.status = QueueStatus::Failed(e.to_string());
+               self.log(line);
+            }
+         }
+      }
+   }
+
+   fn set_paused(&mut self, paused: bool) {
+      self.paused = paused;
+      self.renderer.set_paused(paused);
+      self.log(if paused { "Paused".to_string() } else { "Resumed".to_string() });
+   }
+
+   fn cancel(&mut self, index: usize) {
+      if self.running == Some(index) {
+         self.renderer.cancel();
+         self.running = None;
+      }
+
+      let job = &mut self.jobs[index];
+      job.status = QueueStatus::Cancelled;
+      let line = format!("Cancelled {}", job.name);
+      self.log(line);
+   }
+
+   fn log(&mut self, line: String) {
+      let seconds = self.created.elapsed().as_secs();
+      self.log.push(format!("[{:02}:{:02}] {line}", seconds / 60, seconds % 60));
+
+      if self.log.len() > MAX_LOG_LINES {
+         self.log.remove(0);
+      }
+   }
+
+   /// seconds left for the whole queue, the pending jobs are guessed from the running ones speed
+   fn queue_remaining(&self) -> Option<f32> {
+      let progress = self.renderer.progress()?;
+      if progress.rate <= 0.0 { return None; }
+
+      let pending = self.jobs.iter()
+          .filter(|job| job.status == QueueStatus::Pending)
+          .map(|job| job.settings.width as f64 * job.settings.height as f64 * job.settings.samples_per_pixel.max(1) as f64)
+          .sum::<f64>();
+
+      Some(progress.remaining + (pending / progress.rate) as f32)
+   }
+}
+
+
+////////
+// Ui //
+////////
+impl RenderQueue {
+   pub fn ui(&mut self, ui: &mut Ui, current_scene: &Scene, saved_scenes: &[Scene]) {
+      ui.heading("Render queue");
+
+      // same as the tiled render panel, no file system
+      if cfg!(target_arch = "wasm32") {
+         ui.label("Only available on desktop");
+         return;
+      }
+
+      ui.group(|ui| {
+         ui.label("New job");
+         self.draft_ui(ui, current_scene, saved_scenes);
+
+         if ui.button("Add to queue").clicked() {
+            let scene = self.draft_scene.map_or(current_scene, |i| &saved_scenes[i]);
+            self.push(scene, self.draft_scene.map(scene_name), self.draft_camera, self.draft.clone());
+         }
+      });
+
+      ui.group(|ui| {
+         ui.horizontal(|ui| {
+            let label = if self.paused { "Resume" } else { "Pause" };
+            if ui.button(label).clicked() {
+               self.set_paused(!self.paused);
+            }
+
+            if ui.button("Clear finished").clicked() {
+               self.clear_finished();
+            }
+
+            if let Some(remaining) = self.queue_remaining() {
+               ui.label(format!("Queue: {}", format_seconds(remaining)));
+            }
+         });
+
+         ui.separator();
+         self.jobs_ui(ui);
+      });
+
+      ui.group(|ui| {
+         ui.horizontal(|ui| {
+            ui.label("Log");
+            if ui.small_button("Clear").clicked() { self.log.clear(); }
+         });
+
+         ScrollArea::vertical()
+             .max_height(200.0)
+             .stick_to_bottom(true)
+             .show(ui, |ui| {
+                for line in &self.log {
+                   ui.monospace(line);
+                }
+                ui.set_min_width(ui.available_width());
+             });
+      });
+   }
+
+   fn draft_ui(&mut self, ui: &mut Ui, current_scene: &Scene, saved_scenes: &[Scene]) {
+      if self.draft_scene.is_some_and(|i| i >= saved_scenes.len()) {
+         self.draft_scene = None;
+      }
+
+      let scene_label = |scene: Option<usize>| scene.map_or("Current scene".to_string(), scene_name);
+      let before = self.draft_scene;
+      ComboBox::from_label("Scene")
+          .selected_text(scene_label(self.draft_scene))
+          .show_ui(ui, |ui| {
+             ui.selectable_value(&mut self.draft_scene, None, scene_label(None));
+             for i in 0..saved_scenes.len() {
+                ui.selectable_value(&mut self.draft_scene, Some(i), scene_label(Some(i)));
+             }
+          });
+
+      // camera indices belong to the scene they came from
+      let scene = self.draft_scene.map_or(current_scene, |i| &saved_scenes[i]);
+      if self.draft_scene != before || self.draft_camera.is_some_and(|i| i >= scene.cameras.len()) {
+         self.draft_camera = None;
+      }
+
+      let camera_name = |camera: Option<usize>| match camera {
+         Some(i) => scene.cameras[i].name.clone(),
+         None => "Current view".to_string(),
+      };
+
+      ComboBox::from_label("Camera")
+          .selected_text(camera_name(self.draft_camera))
+          .show_ui(ui, |ui| {
+             ui.selectable_value(&mut self.draft_camera, None, camera_name(None));
+             for i in 0..scene.cameras.len() {
+                ui.selectable_value(&mut self.draft_camera, Some(i), camera_name(Some(i)));
+             }
+          });
+
+      self.draft.ui(ui);
+   }
+
+   fn jobs_ui(&mut self, ui: &mut Ui) {
+      if self.jobs.is_empty() {
+         ui.label("Nothing queued");
+         return;
+      }
+
+      let mut cancel = None;
+      let mut remove = None;
+      let mut move_up = None;
+
+      for (i, job) in self.jobs.iter_mut().enumerate() {
+         ui.horizontal(|ui| {
+            ui.label(format!("{}.", i + 1));
+            ui.label(&job.name);
+            ui.label(format!("{}x{} @ {}", job.settings.width, job.settings.height, job.settings.samples_per_pixel));
+            ui.add(TextEdit::singleline(&mut job.settings.output_path)
+                .desired_width(120.0)
+                .interactive(job.status == QueueStatus::Pending));
+
+            match &job.status {
+               QueueStatus::Pending => {
+                  if i > 0 && ui.small_button("⏶").on_hover_text("Move up").clicked() { move_up = Some(i); }
+                  if ui.small_button("🗑").clicked() { remove = Some(i); }
+               }
+               QueueStatus::Running => {
+                  if let Some(progress) = self.renderer.progress() {
+                     let text = format!(
+                        "Tile {}/{}, {} left",
+                        progress.tile + 1,
+                        progress.tiles,
+                        format_seconds(progress.remaining),
+                     );
+                     ui.add(ProgressBar::new(progress.fraction).desired_width(200.0).text(text));
+                  }
+                  if ui.small_button("Cancel").clicked() { cancel = Some(i); }
+               }
+               QueueStatus::Done { seconds } => { ui.label(format!("Done in {}", format_seconds(*seconds))); }
+               QueueStatus::Failed(e) => { ui.label("Failed").on_hover_text(e); }
+               QueueStatus::Cancelled => { ui.label("Cancelled"); }
+            }
+         });
+      }
+
+      if let Some(i) = cancel { self.cancel(i); }
+      if let Some(i) = move_up { self.jobs.swap(i - 1, i); self.fix_running_after_swap(i - 1, i); }
+      if let Some(i) = remove { self.remove(i); }
+   }
+
+   fn fix_running_after_swap(&mut self, a: usize, b: usize) {
+      if self.running == Some(a) {
+         self.running = Some(b);
+      } else if self.running == Some(b) {
+         self.running = Some(a);
+      }
+   }
+
+   fn remove(&mut self, index: usize) {
+      self.jobs.remove(index);
+      if let Some(running) = &mut self.running {
+         if *running > index { *running -= 1; }
+      }
+   }
+
+   fn clear_finished(&mut self) {
+      let mut index = 0;
+      while index < self.jobs.len() {
+         match self.jobs[index].status {
+            QueueStatus::Pending | QueueStatus::Running => index += 1,
+            _ => self.remove(index),
+         }
+      }
+   }
+}
+
+/// "1h 02m", "3m 04s" or "12s"
+fn format_seconds(seconds: f32) -> String {
+   let seconds = seconds.max(0.0) as u64;
+   match seconds {
+      s if s >= 3600 => format!("{}h {:02}m", s / 3600, (s % 3600) / 60),
+      s if s >= 60 => format!("{}m {:02}s", s / 60, s % 60),
+      s => format!("{s}s"),
+   }
+}
+
+/// saved scenes don't have names, they're told apart by where they are in ``Settings::saved_scenes``
+fn scene_name(index: usize) -> String {
+   format!("Scene {}", index + 1)
+}
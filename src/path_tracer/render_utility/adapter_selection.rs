@@ -0,0 +1,216 @@
+use std::path::PathBuf;
+
+use egui::{CollapsingHeader, Grid, Ui};
+use egui_wgpu::RenderState;
+use wgpu::{Adapter, AdapterInfo, DeviceType, Features, Limits};
+
+use crate::singletons::settings::{AdapterSettings, BackendChoice, PowerChoice};
+use crate::user_interface::ui_modules::enum_combination_box;
+
+/// eframe's app id, the name ``run_native`` gets, its storage file lives under it
+pub const APP_ID: &str = "eframe template";
+/// ``restart()`` hands the settings over with this, the old process exits before eframe finishes writing its storage
+const ADAPTER_ARG: &str = "--adapter";
+
+/// what the settings page shows of an adapter, copied out so the ui doesn't need the render state
+#[derive(Clone)]
+pub struct AdapterDetails {
+   pub info: AdapterInfo,
+   pub features: Features,
+   pub limits: Limits,
+}
+
+impl AdapterDetails {
+   pub fn new(adapter: &Adapter) -> Self {
+      Self {
+         info: adapter.get_info(),
+         features: adapter.features(),
+         limits: adapter.limits(),
+      }
+   }
+}
+
+/// the adapter in use, the ones that could have been and the settings the app started with
+#[derive(Default)]
+pub struct Adapters {
+   pub current: Option<AdapterDetails>,
+   /// empty on web, the browser picks
+   pub available: Vec<AdapterDetails>,
+   pub launched: AdapterSettings,
+}
+
+impl Adapters {
+   pub fn new(render_state: Option<&RenderState>, launched: AdapterSettings) -> Self {
+      let Some(render_state) = render_state else { return Self { launched, ..Self::default() }; };
+
+      #[cfg(not(target_arch = "wasm32"))]
+      let available = render_state.available_adapters.iter().map(AdapterDetails::new).collect();
+      #[cfg(target_arch = "wasm32")]
+      let available = vec![];
+
+      Self {
+         current: Some(AdapterDetails::new(&render_state.adapter)),
+         available,
+         launched,
+      }
+   }
+}
+
+
+/////////////
+// Startup //
+/////////////
+/// settings from the ``--adapter`` flag when restarted with one, otherwise ``stored``
+pub fn launch_settings(stored: AdapterSettings) -> AdapterSettings {
+   let mut args = std::env::args();
+   while let Some(arg) = args.next() {
+      if arg != ADAPTER_ARG { continue; }
+
+      match args.next().map(|json| serde_json::from_str(&json)) {
+         Some(Ok(settings)) => return settings,
+         _ => log::warn!("couldn't read {ADAPTER_ARG}, using the saved adapter settings"),
+      }
+   }
+   stored
+}
+
+/// what ``restart()`` passes the new process
+pub fn restart_args(settings: &AdapterSettings) -> Vec<String> {
+   vec![ADAPTER_ARG.to_string(), serde_json::to_string(settings).unwrap()]
+}
+
+/// the adapter is picked before eframe's storage is open so its settings get their own file next to it,
+/// none on web where the browser picks
+fn settings_path() -> Option<PathBuf> {
+   #[cfg(not(target_arch = "wasm32"))]
+   return Some(eframe::storage_dir(APP_ID)?.join("adapter_settings.json"));
+
+   #[cfg(target_arch = "wasm32")]
+   None
+}
+
+/// adapter settings of the last run
+pub fn stored_settings() -> AdapterSettings {
+   let read = || serde_json::from_str(&std::fs::read_to_string(settings_path()?).ok()?).ok();
+   read().unwrap_or_default()
+}
+
+/// writes what ``stored_settings`` reads next launch
+pub fn save_settings(settings: &AdapterSettings) {
+   let Some(path) = settings_path() else { return; };
+
+   let write = || -> std::io::Result<()> {
+      if let Some(dir) = path.parent() { std::fs::create_dir_all(dir)?; }
+      std::fs::write(&path, serde_json::to_string(settings).map_err(std::io::Error::other)?)
+   };
+   if let Err(e) = write() {
+      log::warn!("couldn't save the adapter settings to {}: {e}", path.display());
+   }
+}
+
+
+////////
+// Ui //
+////////
+impl Adapters {
+   /// returns true when restart was clicked
+   pub fn ui(&self, ui: &mut Ui, settings: &mut AdapterSettings) -> bool {
+      let mut restart = false;
+
+      match &self.current {
+         Some(current) => {
+            ui.label(format!("Running on {} ({:?}, {:?})", current.info.name, current.info.backend, current.info.device_type));
+
+            if let Some(wanted) = self.launched.adapter_name.as_ref().filter(|name| **name != current.info.name) {
+               ui.colored_label(ui.visuals().warn_fg_color, format!("Picked {wanted} but got another one"))
+                   .on_hover_text("Only the backend and power preference can be chosen, try narrowing the backend");
+            }
+         }
+         None => { ui.label("No wgpu adapter"); }
+      }
+
+      if cfg!(target_arch = "wasm32") {
+         ui.label("The browser picks the adapter");
+         return false;
+      }
+
+      ui.label("eframe only lets the backend and power preference be chosen, the driver picks the adapter from those");
+
+      if enum_combination_box(ui, &mut settings.backend, "Backend") { settings.adapter_name = None; }
+      if enum_combination_box(ui, &mut settings.power_preference, "Power preference") { settings.adapter_name = None; }
+
+      if *settings != self.launched {
+         ui.horizontal(|ui| {
+            ui.colored_label(ui.visuals().warn_fg_color, "Applies after a restart");
+            if ui.button("Restart").clicked() { restart = true; }
+         });
+      }
+
+      ui.separator();
+      for (i, adapter) in self.available.iter().enumerate() {
+         adapter_ui(ui, i, adapter, settings, self.current.as_ref());
+      }
+
+      restart
+   }
+}
+
+fn adapter_ui(ui: &mut Ui, index: usize, adapter: &AdapterDetails, settings: &mut AdapterSettings, current: Option<&AdapterDetails>) {
+   let info = &adapter.info;
+   let is_current = current.is_some_and(|c| c.info == *info);
+
+   let title = format!("{} ({:?}){}", info.name, info.backend, if is_current { ", current" } else { "" });
+   CollapsingHeader::new(title)
+       .id_source(("adapter", index))
+       .show(ui, |ui| {
+          let backend = BackendChoice::from_backend(info.backend);
+          ui.add_enabled_ui(backend != BackendChoice::Auto, |ui| {
+             let prefer = ui.button("Prefer this adapter's backend and power")
+                 .on_hover_text("Sets the backend and power preference that usually lead to this adapter, it isn't guaranteed");
+             if prefer.clicked() {
+                settings.backend = backend;
+                settings.power_preference = match info.device_type {
+                   DeviceType::DiscreteGpu => PowerChoice::HighPerformance,
+                   _ => PowerChoice::LowPower,
+                };
+                settings.adapter_name = Some(info.name.clone());
+             }
+          });
+
+          Grid::new(("adapter info", index)).num_columns(2).striped(true).show(ui, |ui| {
+             let mut row = |name: &str, value: String| {
+                ui.label(name);
+                ui.label(value);
+                ui.end_row();
+             };
+
+             row("Type", format!("{:?}", info.device_type));
+             row("Driver", info.driver.clone());
+             row("Driver info", info.driver_info.clone());
+             row("Vendor", format!("0x{:04x}", info.vendor));
+             row("Device", format!("0x{:04x}", info.device));
+
+             let limits = &adapter.limits;
+             row("Max texture 2d", limits.max_texture_dimension_2d.to_string());
+             row("Max buffer", format_bytes(limits.max_buffer_size));
+             row("Max storage binding", format_bytes(limits.max_storage_buffer_binding_size as u64));
+             row("Max bind groups", limits.max_bind_groups.to_string());
+             row("Max workgroup invocations", limits.max_compute_invocations_per_workgroup.to_string());
+          });
+
+          CollapsingHeader::new(format!("Features ({})", adapter.features.iter().count()))
+              .id_source(("adapter features", index))
+              .show(ui, |ui| {
+                 for (name, _) in adapter.features.iter_names() {
+                    ui.monospace(name);
+                 }
+              });
+       });
+}
+
+fn format_bytes(bytes: u64) -> String {
+   match bytes {
+      b if b >= 1 << 30 => format!("{:.1} GiB", b as f64 / (1u64 << 30) as f64),
+      b => format!("{} MiB", b >> 20),
+   }
+}
@@ -0,0 +1,113 @@
+use wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, Buffer, BufferDescriptor, BufferUsages, Device, ShaderStages};
+
+/// size of one ``Aov`` in the shaders, normal + depth, packed albedo, object id, step count and a spare
+pub const AOV_STRIDE: u64 = 32;
+/// size of one ``SampleStats`` in the shaders, luminance second moment and sample count
+pub const SAMPLE_STATS_STRIDE: u64 = 8;
+
+/// per pixel auxiliary outputs written by the path tracer next to the beauty texture,
+/// the compute pass writes it through ``StorageTexturePackage::write_bind_group``
+pub struct AovBufferPackage {
+   pub buffer: Buffer,
+   /// kept across frames for adaptive sampling, reset by the shader when accumulation restarts
+   pub stats_buffer: Buffer,
+   pub size: (u32, u32),
+   /// false when the image is too large for a single storage binding, the shaders
+   /// bounds check against the real length so a stub buffer is bound instead
+   pub available: bool,
+   pub stats_available: bool,
+
+   pub read_bind_group_layout: BindGroupLayout,
+   pub read_bind_group: BindGroup,
+}
+impl AovBufferPackage {
+   pub fn new(device: &Device, size: (u32, u32)) -> Self {
+      let (buffer, available) = per_pixel_buffer(device, size, AOV_STRIDE, "aov_buffer");
+      let (stats_buffer, stats_available) = per_pixel_buffer(device, size, SAMPLE_STATS_STRIDE, "sample_stats_buffer");
+
+      let read_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+         entries: &[
+            wgpu::BindGroupLayoutEntry {
+               binding: 0,
+               visibility: ShaderStages::FRAGMENT | ShaderStages::COMPUTE,
+               ty: read_only_binding_type(),
+               count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+               binding: 1,
+               visibility: ShaderStages::FRAGMENT | ShaderStages::COMPUTE,
+               ty: read_only_binding_type(),
+               count: None,
+            },
+         ],
+         label: Some("aov_bind_group_layout Read Only"),
+      });
+
+      let read_bind_group = device.create_bind_group(&BindGroupDescriptor {
+         layout: &read_bind_group_layout,
+         entries: &[
+            BindGroupEntry {
+               binding: 0,
+               resource: buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+               binding: 1,
+               resource: stats_buffer.as_entire_binding(),
+            },
+         ],
+         label: Some("aov_bind_group Read Only"),
+      });
+
+      Self {
+         buffer,
+         stats_buffer,
+         size,
+         available,
+         stats_available,
+         read_bind_group_layout,
+         read_bind_group,
+      }
+   }
+
+   /// remakes the buffers if the size changed, returns true if they were remade
+   pub fn update(&mut self, device: &Device, size_check: (u32, u32)) -> bool {
+      if self.size != size_check {
+         self.buffer.destroy();
+         self.stats_buffer.destroy();
+         *self = Self::new(device, size_check);
+         return true;
+      }
+      false
+   }
+}
+
+/// ``stride`` bytes per pixel or a single element stub if that doesn't fit in one binding
+fn per_pixel_buffer(device: &Device, size: (u32, u32), stride: u64, label: &str) -> (Buffer, bool) {
+   let wanted = size.0 as u64 * size.1 as u64 * stride;
+   let available = wanted > 0 && wanted <= device.limits().max_storage_buffer_binding_size as u64;
+
+   let buffer = device.create_buffer(&BufferDescriptor {
+      label: Some(label),
+      size: if available { wanted } else { stride },
+      usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+      mapped_at_creation: false,
+   });
+
+   (buffer, available)
+}
+
+pub fn read_only_binding_type() -> wgpu::BindingType {
+   wgpu::BindingType::Buffer {
+      ty: wgpu::BufferBindingType::Storage { read_only: true },
+      has_dynamic_offset: false,
+      min_binding_size: None,
+   }
+}
+
+pub fn read_write_binding_type() -> wgpu::BindingType {
+   wgpu::BindingType::Buffer {
+      ty: wgpu::BufferBindingType::Storage { read_only: false },
+      has_dynamic_offset: false,
+      min_binding_size: None,
+   }
+}
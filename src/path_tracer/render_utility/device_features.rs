@@ -0,0 +1,52 @@
+use egui::Ui;
+use wgpu::{Adapter, DeviceDescriptor, Features, Limits};
+
+/// nothing is required, these are asked for when the adapter has them, with what is lost without
+pub const OPTIONAL_FEATURES: [(Features, &str); 2] = [
+   (Features::TIMESTAMP_QUERY, "Gpu profiler"),
+   (Features::TIMESTAMP_QUERY_INSIDE_ENCODERS, "Nested gpu profiler sections, whole passes are timed instead"),
+];
+
+/// the device descriptor for eframe, built from what ``adapter`` supports so any webgpu adapter starts
+pub fn device_descriptor(adapter: &Adapter) -> DeviceDescriptor<'static> {
+   let wanted = OPTIONAL_FEATURES.iter().fold(Features::empty(), |all, (feature, _)| all | *feature);
+   let missing = wanted - adapter.features();
+   if !missing.is_empty() {
+      log::warn!("adapter is missing {missing:?}, running without them");
+   }
+
+   DeviceDescriptor {
+      label: Some("mgsdfe device"),
+      required_features: adapter.features() & wanted,
+      required_limits: required_limits(adapter),
+   }
+}
+
+/// the webgpu defaults with only the limits the renderer grows into raised to what ``adapter`` allows
+fn required_limits(adapter: &Adapter) -> Limits {
+   let supported = adapter.limits();
+
+   Limits {
+      // viewport renders past this go through the tiled renderer
+      max_texture_dimension_2d: supported.max_texture_dimension_2d,
+      // the per pixel aov and sample stats buffers, they're left out when too big
+      max_storage_buffer_binding_size: supported.max_storage_buffer_binding_size,
+      max_buffer_size: supported.max_buffer_size,
+      ..Limits::default()
+   }
+}
+
+/// lists the optional features and whether the device got them
+pub fn features_ui(ui: &mut Ui, features: Features) {
+   for (feature, used_for) in OPTIONAL_FEATURES {
+      ui.horizontal(|ui| {
+         if features.contains(feature) {
+            ui.label("✔");
+            ui.label(used_for);
+         } else {
+            ui.colored_label(ui.visuals().warn_fg_color, "✖");
+            ui.label(format!("{used_for}, not supported"));
+         }
+      }).response.on_hover_text(format!("{feature:?}"));
+   }
+}
@@ -1,9 +1,15 @@
-use wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, Device, Extent3d, ShaderStages, StorageTextureAccess, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension};
+use wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, Device, Extent3d, ShaderStages, StorageTextureAccess, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension};
+use crate::path_tracer::render_utility::aov_buffer_package::{AovBufferPackage, read_write_binding_type};
 use crate::path_tracer::render_utility::helper_structs::{extent_to_f32, f32_to_extent, Flipper};
 
 pub struct DualStorageTexturePackage {
    pub size: Extent3d,
    pub textures: Flipper<StorageTexturePackage>,
+   /// shared by both textures
+   pub aovs: AovBufferPackage,
+   /// the textures and aovs from the size before, dynamic resolution flips between two sizes
+   /// so going back is a swap and not a reallocation
+   spare: Option<(Flipper<StorageTexturePackage>, AovBufferPackage)>,
 }
 impl DualStorageTexturePackage {
    pub fn new(device: &Device) -> Self {
@@ -13,21 +19,39 @@ impl DualStorageTexturePackage {
          depth_or_array_layers: 1,
       };
 
-      let tex_1 = StorageTexturePackage::new(device, extent_to_f32(&size));
-      let tex_2 = StorageTexturePackage::new(device, extent_to_f32(&size));
+      let aovs = AovBufferPackage::new(device, (size.width, size.height));
+
+      let tex_1 = StorageTexturePackage::new(device, extent_to_f32(&size), &aovs);
+      let tex_2 = StorageTexturePackage::new(device, extent_to_f32(&size), &aovs);
 
       let textures = Flipper::new(tex_1, tex_2);
 
       Self {
          size,
          textures,
+         aovs,
+         spare: None,
       }
    }
 
+   /// switches to ``size``, from the spare if it's that size
    pub fn update(&mut self, device: &Device) {
       let check = (self.size.width, self.size.height);
-      self.textures.one.update(device, check);
-      self.textures.two.update(device, check);
+      if self.aovs.size == check { return; }
+
+      let (textures, aovs) = match self.spare.take() {
+         Some((textures, aovs)) if aovs.size == check => (textures, aovs),
+         _ => {
+            let aovs = AovBufferPackage::new(device, check);
+            let size = extent_to_f32(&self.size);
+            let textures = Flipper::new(StorageTexturePackage::new(device, size, &aovs), StorageTexturePackage::new(device, size, &aovs));
+            (textures, aovs)
+         }
+      };
+
+      let old_textures = std::mem::replace(&mut self.textures, textures);
+      let old_aovs = std::mem::replace(&mut self.aovs, aovs);
+      self.spare = Some((old_textures, old_aovs));
    }
 
    pub fn read_layout(&self) -> &BindGroupLayout {
@@ -51,7 +75,7 @@ pub struct StorageTexturePackage {
    pub write_bind_group: BindGroup,
 }
 impl StorageTexturePackage {
-   pub fn new(device: &Device, in_size: (f32, f32)) -> Self {
+   pub fn new(device: &Device, in_size: (f32, f32), aovs: &AovBufferPackage) -> Self {
       let size = f32_to_extent(&in_size);
 
       let texture_desc = TextureDescriptor {
@@ -61,7 +85,7 @@ impl StorageTexturePackage {
          sample_count: 1,
          dimension: TextureDimension::D2,
          format: TextureFormat::Rgba32Float,
-         usage: TextureUsages::STORAGE_BINDING | TextureUsages::COPY_SRC,
+         usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC,
          view_formats: &[],
       };
 
@@ -71,18 +95,7 @@ impl StorageTexturePackage {
 
       let read_bind_group_layout =
           device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-             entries: &[
-                wgpu::BindGroupLayoutEntry {
-                   binding: 0,
-                   visibility: ShaderStages::FRAGMENT | ShaderStages::COMPUTE,
-                   ty: wgpu::BindingType::StorageTexture {
-                      access: StorageTextureAccess::ReadOnly,
-                      format: TextureFormat::Rgba32Float,
-                      view_dimension: TextureViewDimension::D2,
-                   },
-                   count: None,
-                },
-             ],
+             entries: &[read_texture_entry()],
              label: Some("texture_bind_group_layout Read Only"),
           });
 
@@ -111,6 +124,18 @@ impl StorageTexturePackage {
                    },
                    count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                   binding: 1,
+                   visibility: ShaderStages::COMPUTE,
+                   ty: read_write_binding_type(),
+                   count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                   binding: 2,
+                   visibility: ShaderStages::COMPUTE,
+                   ty: read_write_binding_type(),
+                   count: None,
+                },
              ],
              label: Some("texture_bind_group_layout Write only"),
           });
@@ -122,6 +147,14 @@ impl StorageTexturePackage {
                binding: 0,
                resource: wgpu::BindingResource::TextureView(&view),
             },
+            BindGroupEntry {
+               binding: 1,
+               resource: aovs.buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+               binding: 2,
+               resource: aovs.stats_buffer.as_entire_binding(),
+            },
          ],
          label: Some("diffuse_bind_group  Read Only"),
       });
@@ -139,14 +172,30 @@ impl StorageTexturePackage {
       }
    }
 
-   pub fn update(&mut self, device: &Device, size_check: (u32, u32)) {
-      if (self.texture.width() != size_check.0) | (self.texture.height() != size_check.1) {
+   /// ``aovs_remade`` forces a remake so the write bind group points at the new buffers
+   pub fn update(&mut self, device: &Device, size_check: (u32, u32), aovs: &AovBufferPackage, aovs_remade: bool) {
+      if (self.texture.width() != size_check.0) | (self.texture.height() != size_check.1) | aovs_remade {
          self.texture.destroy();
-         self.remake(device, (size_check.0 as f32, size_check.1 as f32));
+         self.remake(device, (size_check.0 as f32, size_check.1 as f32), aovs);
       }
    }
 
-   pub fn remake(&mut self, device: &Device, size: (f32, f32)) {
-      *self = Self::new(device, size);
+   pub fn remake(&mut self, device: &Device, size: (f32, f32), aovs: &AovBufferPackage) {
+      *self = Self::new(device, size, aovs);
    }
-}
\ No newline at end of file
+}
+
+/// the previous frame is read as a sampled texture, read only storage textures
+/// need ``TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES`` which plenty of adapters don't have
+pub fn read_texture_entry() -> wgpu::BindGroupLayoutEntry {
+   wgpu::BindGroupLayoutEntry {
+      binding: 0,
+      visibility: ShaderStages::FRAGMENT | ShaderStages::COMPUTE,
+      ty: wgpu::BindingType::Texture {
+         sample_type: TextureSampleType::Float { filterable: false },
+         view_dimension: TextureViewDimension::D2,
+         multisampled: false,
+      },
+      count: None,
+   }
+}
@@ -1,22 +1,55 @@
 #![allow(unused_variables, dead_code)]
 
 use egui::ahash::HashMap;
+use egui::{Align2, CollapsingHeader, FontId, Rect, Sense, Stroke, Ui, Vec2};
 use instant::Instant;
-use wgpu::{Buffer, BufferDescriptor, BufferUsages, CommandEncoder, Device, QuerySet, QuerySetDescriptor, QueryType, Queue};
+use wgpu::{Buffer, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder, ComputePassTimestampWrites, Device, Features, Maintain, MapMode, QUERY_SET_MAX_QUERIES, QuerySet, QuerySetDescriptor, QueryType, Queue, RenderPassTimestampWrites};
 use crate::app::PROF;
 
+/// frames that can be waiting on the gpu at once, a frame is skipped if they are all busy
+const READBACK_RING_SIZE: usize = 4;
+
+/// where timestamps can be written on this device
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TimestampMode {
+   /// anywhere in the encoder, sections can hold several passes and nest
+   Encoder,
+   /// only at the start and end of a pass through ``timestamp_writes``, every section is one pass
+   Pass,
+   /// no timestamp queries, the profiler stays inactive
+   Unsupported,
+}
+
 pub struct GpuProfiler {
    pub query_count: u32,
+   pub mode: TimestampMode,
    pub timers: HashMap<String, TimerEntry>,
+   /// keys of the latest read frame in the order they started
+   pub frame_scopes: Vec<String>,
+   /// open sections while recording, the last one is the parent of the next
+   scope_stack: Vec<String>,
+   /// sections recorded this frame, handed to a readback slot on resolve
+   recording: Vec<ScopeRecord>,
 
    count: u32,
+   /// queries this frame asked for, the pool grows to fit on resolve
+   wanted: u32,
+   /// sections that didn't fit last frame
+   dropped: u32,
+   dropping: u32,
    buffer_size: u64,
 
-   pub query_set: QuerySet,
+   query_set: Option<QuerySet>,
    pub query_buffer: Buffer,
-   pub cpu_buffer: Buffer,
+   /// mapped asynchronously and read a few frames later so the cpu never waits on the gpu
+   ring: Vec<ReadbackSlot>,
+   /// next slot to copy into, also the oldest one in flight
+   write_slot: usize,
 
    pub active: bool,
+   /// keeps every read frame for ``take_captured``
+   pub capture: bool,
+   captured: Vec<GpuFrame>,
 
    pub amount: u32,
    pub update_interval: f64,
@@ -24,24 +57,41 @@ pub struct GpuProfiler {
    last_data_dump: Instant,
 }
 impl GpuProfiler {
+   /// ``query_count`` is just the starting size, the pool grows when more sections are used
    pub fn new(device: &Device, query_count: u32) -> Self {
-      let query_set = device.create_query_set(&QuerySetDescriptor {
-         label: Some("Timestamp query"),
-         ty: QueryType::Timestamp,
-         count: query_count,
-      });
-
-      let (query_buffer, cpu_buffer, buffer_size) = Self::init_buffers(device, query_count);
+      let features = device.features();
+      let mode = if features.contains(Features::TIMESTAMP_QUERY_INSIDE_ENCODERS) {
+         TimestampMode::Encoder
+      } else if features.contains(Features::TIMESTAMP_QUERY) {
+         TimestampMode::Pass
+      } else {
+         TimestampMode::Unsupported
+      };
+
+      let query_count = query_count.clamp(2, QUERY_SET_MAX_QUERIES);
+      let query_set = Self::init_query_set(device, mode, query_count);
+      let (query_buffer, buffer_size) = Self::init_query_buffer(device, query_count);
+      let ring = (0..READBACK_RING_SIZE).map(|_| ReadbackSlot::new(device, buffer_size)).collect();
 
       Self {
          query_count,
+         mode,
          timers: HashMap::default(),
+         frame_scopes: vec![],
+         scope_stack: vec![],
+         recording: vec![],
          count: 0,
-         active: true,
+         wanted: 0,
+         dropped: 0,
+         dropping: 0,
+         active: mode != TimestampMode::Unsupported,
+         capture: false,
+         captured: vec![],
          buffer_size,
          query_set,
          query_buffer,
-         cpu_buffer,
+         ring,
+         write_slot: 0,
 
          amount: 50,
          update_interval: 0.25,
@@ -50,7 +100,17 @@ impl GpuProfiler {
       }
    }
 
-   fn init_buffers(device: &Device, count: u32) -> (Buffer, Buffer, u64) {
+   fn init_query_set(device: &Device, mode: TimestampMode, count: u32) -> Option<QuerySet> {
+      if mode == TimestampMode::Unsupported { return None; }
+
+      Some(device.create_query_set(&QuerySetDescriptor {
+         label: Some("Timestamp query"),
+         ty: QueryType::Timestamp,
+         count,
+      }))
+   }
+
+   fn init_query_buffer(device: &Device, count: u32) -> (Buffer, u64) {
       let buffer_size = 8 * count as u64;
 
       let query_buffer = device.create_buffer(&BufferDescriptor {
@@ -63,97 +123,262 @@ impl GpuProfiler {
          mapped_at_creation: false,
       });
 
-      let cpu_buffer = device.create_buffer(&BufferDescriptor {
-         label: Some("query_buffer"),
-         size: buffer_size,
-         usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
-         mapped_at_creation: false,
-      });
+      (query_buffer, buffer_size)
+   }
+
+   /// frames read since the last call while ``capture`` was on
+   pub fn take_captured(&mut self) -> Vec<GpuFrame> {
+      std::mem::take(&mut self.captured)
+   }
 
-      (query_buffer, cpu_buffer, buffer_size)
+   /// sections that didn't fit in the pool last frame, they show up once it has grown
+   pub fn dropped(&self) -> u32 {
+      self.dropped
+   }
+
+   /// false when there is no room for both ends of a section, the end of every open
+   /// section is kept free so starting one can never leave another without an end
+   fn reserve_pair(&mut self) -> bool {
+      let open = self.scope_stack.len() as u32;
+      self.wanted = self.wanted.max(self.count + open + 2);
+
+      if self.count + open + 2 > self.query_count {
+         self.dropping += 1;
+         return false;
+      }
+      true
    }
 
    pub fn start_timer(&mut self, encoder: &mut CommandEncoder, key: &str) {
       #[cfg(not(target_arch = "wasm32"))]
-      if self.active {
-         if self.count < self.query_count {
-            encoder.write_timestamp(&self.query_set, self.count);
-
-            self.check_add_timer_entry(key);
-            self.timers.get_mut(key).unwrap().start_index = self.count;
+      if self.active && self.mode == TimestampMode::Encoder {
+         if !self.reserve_pair() { return; }
+         let Some(query_set) = &self.query_set else { return; };
+
+         encoder.write_timestamp(query_set, self.count);
+
+         self.check_add_timer_entry(key);
+         self.recording.push(ScopeRecord {
+            key: key.to_string(),
+            parent: self.scope_stack.last().cloned(),
+            depth: self.scope_stack.len() as u32,
+            start_index: self.count,
+            end_index: None,
+         });
+         self.scope_stack.push(key.to_string());
 
-            self.count += 1;
-         } else {
-            panic!("Warning: Query count exceeded the available queries.");
-         }
+         self.count += 1;
       }
    }
 
    pub fn end_timer(&mut self, encoder: &mut CommandEncoder, key: &str) {
       #[cfg(not(target_arch = "wasm32"))]
-      if self.active {
-         if self.count < self.query_count {
-            encoder.write_timestamp(&self.query_set, self.count);
+      if self.active && self.mode == TimestampMode::Encoder {
+         // the start was dropped
+         let Some(i) = self.scope_stack.iter().rposition(|k| k == key) else { return; };
+         let Some(query_set) = &self.query_set else { return; };
 
-            self.check_add_timer_entry(key);
-            self.timers.get_mut(key).unwrap().end_index = self.count;
+         encoder.write_timestamp(query_set, self.count);
 
-            self.count += 1;
-         } else {
-            panic!("Warning: Query count exceeded the available queries.");
-         }
+         let open = self.recording.iter_mut().rev().find(|r| r.key == key && r.end_index.is_none());
+         if let Some(record) = open { record.end_index = Some(self.count); }
+
+         // closes anything left open inside it too
+         self.scope_stack.truncate(i);
+
+         self.count += 1;
       }
    }
 
+   /// both ends of a section in ``TimestampMode::Pass``, none otherwise
+   fn pass_writes(&mut self, key: &str) -> Option<(&QuerySet, u32, u32)> {
+      if !self.active || self.mode != TimestampMode::Pass || cfg!(target_arch = "wasm32") { return None; }
+      if !self.reserve_pair() { return None; }
+
+      self.check_add_timer_entry(key);
+      self.recording.push(ScopeRecord {
+         key: key.to_string(),
+         parent: None,
+         depth: 0,
+         start_index: self.count,
+         end_index: Some(self.count + 1),
+      });
+      self.count += 2;
+
+      self.query_set.as_ref().map(|q| (q, self.count - 2, self.count - 1))
+   }
+
+   /// times the whole pass as ``key`` when the encoder can't take timestamps, goes in ``timestamp_writes``
+   pub fn compute_pass_writes(&mut self, key: &str) -> Option<ComputePassTimestampWrites<'_>> {
+      self.pass_writes(key).map(|(query_set, start, end)| ComputePassTimestampWrites {
+         query_set,
+         beginning_of_pass_write_index: Some(start),
+         end_of_pass_write_index: Some(end),
+      })
+   }
+
+   /// render pass version of ``compute_pass_writes``
+   pub fn render_pass_writes(&mut self, key: &str) -> Option<RenderPassTimestampWrites<'_>> {
+      self.pass_writes(key).map(|(query_set, start, end)| RenderPassTimestampWrites {
+         query_set,
+         beginning_of_pass_write_index: Some(start),
+         end_of_pass_write_index: Some(end),
+      })
+   }
+
+   /// latest read time in ms for ``key``, a few frames old, none when inactive or never recorded
+   pub fn latest(&self, key: &str) -> Option<f64> {
+      if !self.active { return None; }
+      self.timers.get(key).map(|t| t.latest)
+   }
+
    fn check_add_timer_entry(&mut self, key: &str) {
       if !self.timers.contains_key(key) { self.timers.insert(key.to_string(), TimerEntry::default()); };
    }
 
+   /// scopes of the latest read frame directly inside ``parent``, ``None`` gives the top level
+   pub fn children<'a>(&'a self, parent: Option<&'a str>) -> impl Iterator<Item = &'a String> + 'a {
+      self.frame_scopes.iter().filter(move |k| self.timers[k.as_str()].parent.as_deref() == parent)
+   }
+
+   /// smoothed time of ``key`` minus the time of the scopes inside it
+   pub fn self_time(&self, key: &str) -> f64 {
+      let children = self.children(Some(key)).map(|k| self.timers[k.as_str()].average()).sum::<f64>();
+      (self.timers[key].average() - children).max(0.0)
+   }
+
+   /// copies this frame's queries into the next free readback slot, must be submitted before ``update``
    pub fn resolve(&mut self, encoder: &mut CommandEncoder, device: &Device) {
       #[cfg(not(target_arch = "wasm32"))]
       if self.active {
-         encoder.resolve_query_set(&self.query_set, 0..self.query_count, &self.query_buffer, 0);
-
-         encoder.copy_buffer_to_buffer(&self.query_buffer, 0, &self.cpu_buffer, 0, self.buffer_size);
+         let scopes = std::mem::take(&mut self.recording);
+         let slot = &mut self.ring[self.write_slot];
+
+         // every slot still waiting on the gpu, this frame just goes unmeasured
+         if let (SlotState::Free, Some(query_set)) = (&slot.state, &self.query_set) {
+            if self.count > 0 {
+               // made before the pool last grew
+               if slot.buffer.size() < self.buffer_size {
+                  *slot = ReadbackSlot::new(device, self.buffer_size);
+               }
+
+               encoder.resolve_query_set(query_set, 0..self.count, &self.query_buffer, 0);
+               encoder.copy_buffer_to_buffer(&self.query_buffer, 0, &slot.buffer, 0, self.count as u64 * 8);
+
+               slot.scopes = scopes;
+               slot.submitted = Instant::now();
+               slot.state = SlotState::Copied;
+               self.write_slot = (self.write_slot + 1) % self.ring.len();
+            }
+         }
+         self.scope_stack.clear();
 
-         if self.count < self.query_count {
-            println!("Change query count on gpu profiler to {}", self.count);
-            *self = Self::new(device, self.count);
+         // the queries already in the encoder use the old set, so only swap it after resolving them
+         if self.wanted > self.query_count {
+            self.grow(device, self.wanted);
          }
 
+         self.dropped = self.dropping;
+         self.dropping = 0;
+         self.wanted = 0;
          self.count = 0;
       }
    }
 
+   /// the readback slots grow as they free up
+   fn grow(&mut self, device: &Device, wanted: u32) {
+      let query_count = wanted.next_power_of_two().min(QUERY_SET_MAX_QUERIES);
+      if query_count <= self.query_count { return; }
+
+      log::info!("growing gpu profiler query pool to {query_count}");
+
+      self.query_count = query_count;
+      self.query_set = Self::init_query_set(device, self.mode, query_count);
+      (self.query_buffer, self.buffer_size) = Self::init_query_buffer(device, query_count);
+   }
+
+   /// maps the slots copied into since the last call and reads the ones the gpu is done with, never waits
    #[triglyceride::time_event(PROF, "PULL_TIME_DATA")]
-   fn read_data(&mut self, queue: &Queue, device: &Device) -> Vec<(String, f64)> {
-      let timeings: Vec<u64> = read_buffer_to_vec(device, &self.cpu_buffer).expect("failed to read data");
+   fn read_data(&mut self, queue: &Queue, device: &Device) -> Vec<(Instant, Vec<TimerReading>)> {
+      for slot in self.ring.iter_mut() {
+         if matches!(slot.state, SlotState::Copied) {
+            let (sender, receiver) = flume::bounded(1);
+            slot.buffer.slice(..).map_async(MapMode::Read, move |v| { let _ = sender.send(v); });
+            slot.state = SlotState::Mapping(receiver);
+         }
+      }
+      device.poll(Maintain::Poll);
+
       let period = queue.get_timestamp_period() as f64;
+      let to_ms = |ticks: u64| nanoseconds_to_milliseconds(ticks as f64 * period);
+
+      // oldest first so the graphs stay in order
+      let mut frames = vec![];
+      for i in 0..self.ring.len() {
+         let slot = &mut self.ring[(self.write_slot + i) % READBACK_RING_SIZE];
+
+         let SlotState::Mapping(receiver) = &slot.state else { continue; };
+         let Ok(result) = receiver.try_recv() else { continue; };
+
+         if result.is_ok() {
+            let data = slot.buffer.slice(..).get_mapped_range();
+            let timeings: &[u64] = bytemuck::cast_slice(&data);
+
+            let frame_start = slot.scopes.iter().map(|r| timeings[r.start_index as usize]).min().unwrap_or(0);
 
-      let mut out = vec![];
-      for e in self.timers.iter() {
-         let te = e.1;
+            let mut readings = vec![];
+            for record in slot.scopes.drain(..) {
+               // left open, nothing to measure
+               let Some(end_index) = record.end_index else { continue; };
 
-         let st = timeings[te.start_index as usize];
-         let et = timeings[te.end_index as usize];
+               let st = timeings[record.start_index as usize];
+               let et = timeings[end_index as usize];
 
-         let time = (et - st) as f64 * period;
+               readings.push(TimerReading {
+                  offset_ms: to_ms(st.saturating_sub(frame_start)),
+                  time_ms: to_ms(et.saturating_sub(st)),
+                  record,
+               });
+            }
+            frames.push((slot.submitted, readings));
 
-         let time_ms = nanoseconds_to_milliseconds(time);
+            drop(data);
+            slot.buffer.unmap();
+         }
 
-         out.push((e.0.clone(), time_ms));
+         slot.scopes.clear();
+         slot.state = SlotState::Free;
       }
 
-      out
+      frames
    }
 
    pub fn update(&mut self, queue: &Queue, device: &Device) {
       if self.active {
-         let timeings = self.read_data(queue, device);
+         for (submitted, timeings) in self.read_data(queue, device) {
+            if self.capture {
+               self.captured.push(GpuFrame {
+                  submitted,
+                  scopes: timeings.iter().map(|e| GpuScope {
+                     key: e.record.key.clone(),
+                     offset_ms: e.offset_ms,
+                     time_ms: e.time_ms,
+                  }).collect(),
+               });
+            }
 
-         timeings.iter().for_each(|e| {
-            self.timers.get_mut(e.0.as_str()).unwrap().add_cash(e.1, self.max_cash);
-         });
+            self.frame_scopes.clear();
+            timeings.into_iter().for_each(|e| {
+               let timer = self.timers.get_mut(e.record.key.as_str()).unwrap();
+               timer.latest = e.time_ms;
+               timer.latest_offset = e.offset_ms;
+               timer.parent = e.record.parent;
+               timer.depth = e.record.depth;
+               timer.add_cash(e.time_ms, self.max_cash);
+
+               self.frame_scopes.push(e.record.key);
+            });
+         }
 
          if self.last_data_dump.elapsed().as_secs_f64() > self.update_interval {
             self.last_data_dump = Instant::now();
@@ -167,17 +392,88 @@ impl GpuProfiler {
 }
 
 
+/// a frame read back while ``capture`` was on
+pub struct GpuFrame {
+   /// cpu time the queries were resolved, the closest the cpu clock gets to the gpu one
+   pub submitted: Instant,
+   pub scopes: Vec<GpuScope>,
+}
+
+pub struct GpuScope {
+   pub key: String,
+   /// ms after the first section of the frame started
+   pub offset_ms: f64,
+   pub time_ms: f64,
+}
+
+/// one section as recorded, the query indexes are only valid for the frame it's from
+struct ScopeRecord {
+   key: String,
+   parent: Option<String>,
+   depth: u32,
+   start_index: u32,
+   end_index: Option<u32>,
+}
+
+struct TimerReading {
+   record: ScopeRecord,
+   offset_ms: f64,
+   time_ms: f64,
+}
+
+struct ReadbackSlot {
+   buffer: Buffer,
+   /// what the queries copied in belong to
+   scopes: Vec<ScopeRecord>,
+   submitted: Instant,
+   state: SlotState,
+}
+
+impl ReadbackSlot {
+   fn new(device: &Device, size: u64) -> Self {
+      Self {
+         buffer: device.create_buffer(&BufferDescriptor {
+            label: Some("query_readback_buffer"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+         }),
+         scopes: vec![],
+         submitted: Instant::now(),
+         state: SlotState::Free,
+      }
+   }
+}
+
+enum SlotState {
+   Free,
+   /// copy recorded, mapped on the next ``update`` once it has been submitted
+   Copied,
+   Mapping(flume::Receiver<Result<(), BufferAsyncError>>),
+}
+
 /// edit for performance, smooths out average
 
 pub struct TimerEntry {
-   start_index: u32,
-   end_index: u32,
    elapsed_cash: Vec<f64>,
    index_counter: u32,
+   /// unsmoothed, for things that react to the frame time
+   latest: f64,
+   /// ms from the first scope of the frame to this one starting
+   latest_offset: f64,
+
+   /// the section this one was started inside of
+   pub parent: Option<String>,
+   pub depth: u32,
 
    pub time_graphing: Vec<[f64; 2]>,
 }
 impl TimerEntry {
+   /// last graphed average, or the last frame before the first average is in
+   pub fn average(&self) -> f64 {
+      self.time_graphing.last().map_or(self.latest, |e| e[1])
+   }
+
    pub fn add_cash(&mut self, data: f64, max_cash: u32) {
       if self.elapsed_cash.len() < max_cash as usize {
          self.elapsed_cash.push(data);
@@ -208,15 +504,103 @@ impl TimerEntry {
 impl Default for TimerEntry {
    fn default() -> Self {
       Self {
-         start_index: 0,
-         end_index: 0,
          elapsed_cash: vec![],
          index_counter: 0,
+         latest: 0.0,
+         latest_offset: 0.0,
+         parent: None,
+         depth: 0,
          time_graphing: vec![],
       }
    }
 }
 
+////////
+// Ui //
+////////
+impl GpuProfiler {
+   /// nested scopes with their total and self time
+   pub fn scope_tree_ui(&self, ui: &mut Ui) {
+      if self.frame_scopes.is_empty() {
+         ui.label("No scopes recorded");
+         return;
+      }
+
+      ui.label("total / self");
+      for key in self.children(None) {
+         self.scope_node_ui(ui, key);
+      }
+   }
+
+   fn scope_node_ui(&self, ui: &mut Ui, key: &str) {
+      let label = format!("{:.2}ms / {:.2}ms => {}", self.timers[key].average(), self.self_time(key), key);
+
+      if self.children(Some(key)).next().is_none() {
+         ui.label(label);
+         return;
+      }
+
+      CollapsingHeader::new(label)
+          .id_source(key)
+          .default_open(true)
+          .show(ui, |ui| {
+             for child in self.children(Some(key)) {
+                self.scope_node_ui(ui, child);
+             }
+          });
+   }
+
+   /// icicle chart of last frame, top level scopes on the top row and their children below
+   pub fn flame_graph_ui(&self, ui: &mut Ui, width: f32) {
+      const ROW_HEIGHT: f32 = 20.0;
+
+      let rows = self.frame_scopes.iter().map(|k| self.timers[k.as_str()].depth + 1).max().unwrap_or(1);
+      let (rect, response) = ui.allocate_exact_size(Vec2::new(width, rows as f32 * ROW_HEIGHT), Sense::hover());
+      let painter = ui.painter_at(rect);
+
+      let span = self.frame_scopes.iter()
+          .map(|k| self.timers[k.as_str()].latest_offset + self.timers[k.as_str()].latest)
+          .fold(0.0, f64::max);
+      if span <= 0.0 { return; }
+
+      let visuals = ui.visuals();
+      painter.rect_filled(rect, 0.0, visuals.extreme_bg_color);
+
+      for (i, key) in self.frame_scopes.iter().enumerate() {
+         let timer = &self.timers[key.as_str()];
+
+         let x = rect.min.x + (timer.latest_offset / span) as f32 * rect.width();
+         let w = ((timer.latest / span) as f32 * rect.width()).max(1.0);
+         let y = rect.min.y + timer.depth as f32 * ROW_HEIGHT;
+         let bar = Rect::from_min_size(egui::pos2(x, y), Vec2::new(w, ROW_HEIGHT - 1.0));
+
+         // alternate so neighbours on a row are told apart
+         let fill = if i % 2 == 0 { visuals.selection.bg_fill } else { visuals.widgets.active.bg_fill };
+         painter.rect(bar, 2.0, fill, Stroke::new(1.0, visuals.extreme_bg_color));
+
+         // only label bars wide enough to read
+         if w > 60.0 {
+            painter.with_clip_rect(bar).text(
+               bar.left_center() + Vec2::new(4.0, 0.0),
+               Align2::LEFT_CENTER,
+               format!("{key} {:.2}ms", timer.latest),
+               FontId::proportional(12.0),
+               visuals.strong_text_color(),
+            );
+         }
+
+         if response.hovered() && response.hover_pos().is_some_and(|p| bar.contains(p)) {
+            response.clone().on_hover_text(format!(
+               "{key}\ntotal {:.3}ms\nself {:.3}ms",
+               timer.latest,
+               self.self_time(key),
+            ));
+         }
+      }
+   }
+}
+
+
 pub fn read_buffer_to_vec<T: bytemuck::Pod>(device: &Device, buffer: &Buffer) -> Option<Vec<T>> {
    let buffer_slice = buffer.slice(..);
    let (sender, receiver) = flume::bounded(1);
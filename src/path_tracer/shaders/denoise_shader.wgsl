@@ -0,0 +1,135 @@
+// one à-trous pass, run a few times with a doubling step width
+// https://jo.dreggn.org/home/2010_atrous.pdf
+
+@group(0) @binding(0)
+var input_texture: texture_2d<f32>;
+
+@group(1) @binding(0)
+var output_texture: texture_storage_2d<rgba32float, write>;
+
+// written by the path tracer, see ``Aov`` in testing.glsl
+struct Aov {
+    normal_depth: vec4<f32>,
+    albedo: u32,
+    object_id: i32,
+    steps: u32,
+    _pad: u32,
+}
+@group(2) @binding(0)
+var<storage, read> aovs: array<Aov>;
+
+struct DenoisePass {
+    step_width: i32,
+    color_phi: f32,
+    normal_phi: f32,
+    depth_phi: f32,
+    albedo_phi: f32,
+}
+@group(3) @binding(0)
+var<uniform> pass_settings: DenoisePass;
+
+// 1D B3 spline, the 5x5 kernel is the outer product
+// naga won't index a const array with a runtime value so it's a function
+fn kernel(offset: i32) -> f32 {
+    switch abs(offset) {
+        case 0: { return 0.375; }
+        case 1: { return 0.25; }
+        default: { return 0.0625; }
+    }
+}
+
+struct Guide {
+    valid: bool,
+    hit: bool,
+    normal: vec3<f32>,
+    depth: f32,
+    albedo: vec3<f32>,
+}
+
+fn load_guide(pos: vec2<i32>, dimensions: vec2<i32>) -> Guide {
+    var guide: Guide;
+    let index = u32(pos.y * dimensions.x + pos.x);
+
+    // no aovs at this resolution, fall back to color only
+    guide.valid = index < arrayLength(&aovs);
+    if (!guide.valid) {
+        return guide;
+    }
+
+    let aov = aovs[index];
+    guide.hit = aov.object_id >= 0;
+    guide.normal = aov.normal_depth.xyz;
+    guide.depth = aov.normal_depth.w;
+    guide.albedo = unpack4x8unorm(aov.albedo).rgb;
+    return guide;
+}
+
+fn guide_weight(center: Guide, other: Guide, distance: f32) -> f32 {
+    if (!center.valid || !other.valid) {
+        return 1.0;
+    }
+
+    // never blur geometry into the background or the other way round
+    if (center.hit != other.hit) {
+        return 0.0;
+    }
+    if (!center.hit) {
+        return 1.0;
+    }
+
+    let w_normal = pow(max(dot(center.normal, other.normal), 0.0), pass_settings.normal_phi);
+
+    let depth_scale = pass_settings.depth_phi * max(center.depth, 0.001) * distance + 0.0001;
+    let w_depth = exp(-abs(center.depth - other.depth) / depth_scale);
+
+    let albedo_diff = center.albedo - other.albedo;
+    let w_albedo = exp(-dot(albedo_diff, albedo_diff) / (pass_settings.albedo_phi * pass_settings.albedo_phi));
+
+    return w_normal * w_depth * w_albedo;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dimensions = vec2<i32>(textureDimensions(input_texture));
+    let pos = vec2<i32>(id.xy);
+    if (pos.x >= dimensions.x || pos.y >= dimensions.y) { return; }
+
+    let center_color = textureLoad(input_texture, pos, 0);
+    let center_guide = load_guide(pos, dimensions);
+
+    // the alpha is the object id for the selection outline, keep it as is
+    if (pass_settings.color_phi <= 0.0) {
+        textureStore(output_texture, pos, center_color);
+        return;
+    }
+
+    let color_phi2 = pass_settings.color_phi * pass_settings.color_phi;
+
+    var sum = vec3(0.0);
+    var weight_sum = 0.0;
+
+    for (var y = -2; y <= 2; y++) {
+        for (var x = -2; x <= 2; x++) {
+            let offset = vec2(x, y) * pass_settings.step_width;
+            let sample_pos = clamp(pos + offset, vec2(0, 0), dimensions - vec2(1, 1));
+
+            let color = textureLoad(input_texture, sample_pos, 0).rgb;
+            let guide = load_guide(sample_pos, dimensions);
+
+            let color_diff = center_color.rgb - color;
+            let w_color = exp(-dot(color_diff, color_diff) / color_phi2);
+
+            var weight = kernel(x) * kernel(y) * w_color * guide_weight(center_guide, guide, length(vec2<f32>(offset)));
+            // a zero normal (nothing written there) weights the center against itself to 0
+            if (x == 0 && y == 0) {
+                weight = kernel(0) * kernel(0);
+            }
+
+            sum += color * weight;
+            weight_sum += weight;
+        }
+    }
+
+    // the center tap is forced to its kernel weight so this is never zero
+    textureStore(output_texture, pos, vec4(sum / weight_sum, center_color.a));
+}
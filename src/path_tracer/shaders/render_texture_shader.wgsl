@@ -20,14 +20,45 @@ fn vs_main(model: VertexInput,
 
 
 @group(0) @binding(0)
-var read_texture: texture_storage_2d<rgba32float, read>;
+var read_texture: texture_2d<f32>;
 
 struct DisplaySettings {
     sample_type: u32,
+    selected_object: i32,
+    view_mode: u32,
+    max_steps: u32,
+    depth_far: f32,
+    nominal_samples: u32,
 }
 @group(1) @binding(0)
 var<uniform> dis_set: DisplaySettings;
 
+// written by the path tracer, see ``Aov`` in testing.glsl
+struct Aov {
+    normal_depth: vec4<f32>,
+    albedo: u32,
+    object_id: i32,
+    steps: u32,
+    _pad: u32,
+}
+@group(2) @binding(0)
+var<storage, read> aovs: array<Aov>;
+
+struct SampleStats {
+    lum_m2: f32,
+    samples: u32,
+}
+@group(2) @binding(1)
+var<storage, read> stats: array<SampleStats>;
+
+const VIEW_BEAUTY: u32 = 0u;
+const VIEW_NORMAL: u32 = 1u;
+const VIEW_DEPTH: u32 = 2u;
+const VIEW_ALBEDO: u32 = 3u;
+const VIEW_STEPS: u32 = 4u;
+const VIEW_OBJECT_ID: u32 = 5u;
+const VIEW_SAMPLES: u32 = 6u;
+
 @fragment
 fn fs_main(
     in: VertexOutput,
@@ -36,7 +67,9 @@ fn fs_main(
 
     var color: vec3<f32>;
 
-    if (dis_set.sample_type == 0u) {
+    if (dis_set.view_mode != VIEW_BEAUTY) {
+        color = aov_view(uv);
+    } else if (dis_set.sample_type == 0u) {
         color = nns(uv);
     } else if (dis_set.sample_type == 1u) {
         color = bilinear(uv);
@@ -48,16 +81,106 @@ fn fs_main(
     // the storage texture package to include a texture binging aswell as read/write
     // then have the sampler in display_texture_pipeline
 
+    if (dis_set.selected_object >= 0 && on_selection_edge(uv)) {
+        color = vec3<f32>(1.0, 0.6, 0.1);
+    }
 
     return vec4(color,  1.0);
 }
 
+fn aov_view(uv: vec2<f32>) -> vec3<f32> {
+    let dimensions = textureDimensions(read_texture);
+    let pos = min(vec2<u32>(floor(uv * vec2<f32>(dimensions.xy))), dimensions.xy - vec2(1u, 1u));
+    let index = pos.y * dimensions.x + pos.x;
+
+    // blue is under sampled, green as many as without adaptive sampling, red more
+    if (dis_set.view_mode == VIEW_SAMPLES) {
+        if (index >= arrayLength(&stats)) { return vec3(0.0); }
+        return heatmap(f32(stats[index].samples) / f32(dis_set.nominal_samples * 2u));
+    }
+
+    // buffer too big for this device, a stub is bound instead
+    if (index >= arrayLength(&aovs)) {
+        return vec3(0.0);
+    }
+    let aov = aovs[index];
+
+    switch dis_set.view_mode {
+        case VIEW_NORMAL: {
+            if (aov.object_id < 0) { return vec3(0.0); }
+            return aov.normal_depth.xyz * 0.5 + 0.5;
+        }
+        case VIEW_DEPTH: {
+            return vec3(1.0 - clamp(aov.normal_depth.w / dis_set.depth_far, 0.0, 1.0));
+        }
+        case VIEW_ALBEDO: {
+            return unpack4x8unorm(aov.albedo).rgb;
+        }
+        case VIEW_STEPS: {
+            return heatmap(f32(aov.steps) / f32(max(dis_set.max_steps, 1u)));
+        }
+        case VIEW_OBJECT_ID: {
+            if (aov.object_id < 0) { return vec3(0.0); }
+            return id_color(aov.object_id);
+        }
+        default: {
+            return vec3(0.0);
+        }
+    }
+}
+
+// black -> blue -> green -> yellow -> red
+fn heatmap(t: f32) -> vec3<f32> {
+    let x = clamp(t, 0.0, 1.0);
+    return clamp(vec3(
+        1.5 - abs(4.0 * x - 4.0),
+        1.5 - abs(4.0 * x - 2.5),
+        1.5 - abs(4.0 * x - 1.0),
+    ), vec3(0.0), vec3(1.0)) * min(x * 8.0, 1.0);
+}
+
+// stable distinct color per id
+fn id_color(id: i32) -> vec3<f32> {
+    let hue = fract(f32(id) * 0.618034);
+    let k = fract(vec3(hue) + vec3(1.0, 2.0 / 3.0, 1.0 / 3.0)) * 6.0 - 3.0;
+    return clamp(abs(k) - 1.0, vec3(0.0), vec3(1.0)) * 0.8 + 0.2;
+}
+
+// the object id is stored in the alpha channel by the path tracer
+fn object_id(pos: vec2<i32>) -> i32 {
+    let dimensions = vec2<i32>(textureDimensions(read_texture));
+    let clamped = clamp(pos, vec2(0, 0), dimensions - vec2(1, 1));
+    return i32(round(textureLoad(read_texture, clamped, 0).a));
+}
+
+fn on_selection_edge(uv: vec2<f32>) -> bool {
+    let dimensions = textureDimensions(read_texture);
+    let center = vec2<i32>(floor(uv * vec2<f32>(dimensions.xy)));
+
+    // scale the outline with the texture so it stays visible at high resolutions
+    let width = max(1, i32(dimensions.x) / 640);
+
+    let selected = dis_set.selected_object;
+    let is_selected = object_id(center) == selected;
+
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let other = object_id(center + vec2(x, y) * width) == selected;
+            if (other != is_selected) {
+                return true;
+            }
+        }
+    }
+
+    return false;
+}
+
 fn nns(uv: vec2<f32>) -> vec3<f32> {
     let dimensions = textureDimensions(read_texture);
 
     let uv_nearest = vec2<i32>(floor(uv * vec2<f32>(dimensions.xy)));
 
-    var color = textureLoad(read_texture, uv_nearest).rgb;
+    var color = textureLoad(read_texture, uv_nearest, 0).rgb;
 
     return color;
 }
@@ -66,10 +189,10 @@ fn bilinear(uv: vec2<f32>) -> vec3<f32> {
     let dimensions = textureDimensions(read_texture);
     let uv_nearest = vec2<i32>(floor(uv * vec2<f32>(dimensions.xy)));
 
-    let one = textureLoad(read_texture, uv_nearest + vec2(-1, -1)).rgb;
-    let two = textureLoad(read_texture, uv_nearest + vec2(1, -1)).rgb;
-    let three = textureLoad(read_texture, uv_nearest + vec2(-1, 1)).rgb;
-    let four = textureLoad(read_texture, uv_nearest + vec2(1, 1)).rgb;
+    let one = textureLoad(read_texture, uv_nearest + vec2(-1, -1), 0).rgb;
+    let two = textureLoad(read_texture, uv_nearest + vec2(1, -1), 0).rgb;
+    let three = textureLoad(read_texture, uv_nearest + vec2(-1, 1), 0).rgb;
+    let four = textureLoad(read_texture, uv_nearest + vec2(1, 1), 0).rgb;
 
     return (one + two + three + four) / 4.0;
 }
\ No newline at end of file
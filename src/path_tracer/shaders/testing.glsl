@@ -2,9 +2,32 @@
 
 layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
 
-layout(set = 0, binding = 0, rgba32f) readonly uniform image2D read_tex;
+layout(set = 0, binding = 0) uniform texture2D read_tex;
 layout(set = 1, binding = 0, rgba32f) writeonly uniform image2D write_tex;
 
+// debug outputs, only the first sample of the latest frame, never accumulated
+struct Aov {
+    vec4 normal_depth;
+    uint albedo;
+    int object_id;
+    uint steps;
+    uint _pad;
+};
+
+layout(set = 1, binding = 1, std430) buffer Aovs {
+    Aov aovs[];
+};
+
+// running per pixel statistics, survive between frames unlike the aovs
+struct SampleStats {
+    float lum_m2;
+    uint samples;
+};
+
+layout(set = 1, binding = 2, std430) buffer Stats {
+    SampleStats stats[];
+};
+
 layout(set = 2, binding = 0) uniform PathTracerUniformSettings {
     float time;
     int frame;
@@ -14,7 +37,9 @@ layout(set = 2, binding = 0) uniform PathTracerUniformSettings {
     int steps_per_ray;
 
     int bounces;
+    int projection;
     float fov;
+    float ortho_height;
 
     float pos_x;
     float pos_y;
@@ -23,15 +48,91 @@ layout(set = 2, binding = 0) uniform PathTracerUniformSettings {
     float rot_x;
     float rot_y;
     float rot_z;
+
+    float aperture_radius;
+    float focal_distance;
+    int bokeh_blades;
+
+    int adaptive_sampling;
+    float noise_threshold;
+    int min_adaptive_samples;
+
+    int tile_offset_x;
+    int tile_offset_y;
+    int image_width;
+    int image_height;
 } s;
 
+#define MAX_OBJECTS 32
+
+struct Object {
+    int shape;
+    float data_x;
+    float data_y;
+    float data_z;
+
+    float pos_x;
+    float pos_y;
+    float pos_z;
+
+    float rot_x;
+    float rot_y;
+    float rot_z;
+
+    float scale;
+
+    float col_r;
+    float col_g;
+    float col_b;
+
+    float _pad_0;
+    float _pad_1;
+};
+
+layout(set = 3, binding = 0) uniform SceneObjects {
+    int object_count;
+    int _pad_0;
+    int _pad_1;
+    int _pad_2;
+
+    Object objects[MAX_OBJECTS];
+} o;
+
 
 struct Ray { vec3 ro; vec3 rd; };
-struct Hit { float d;  };
+struct Hit { float d; int id; };
 
 
 #define FP 200.0
 #define MHD 0.001
+#define PI 3.14159265
+
+#define PROJECTION_PERSPECTIVE 0
+#define PROJECTION_ORTHOGRAPHIC 1
+#define PROJECTION_EQUIRECTANGULAR 2
+#define PROJECTION_FISHEYE 3
+
+// most samples a noisy pixel can take in one frame, as a multiple of samples_per_frame
+#define ADAPTIVE_MAX_BOOST 4
+
+
+//////////////
+/// Random ///
+//////////////
+
+uint rng_state;
+
+// pcg hash
+uint pcg() {
+    uint state = rng_state * 747796405u + 2891336453u;
+    uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    rng_state = (word >> 22u) ^ word;
+    return rng_state;
+}
+
+float rand() {
+    return float(pcg()) / 4294967295.0;
+}
 
 //////////////
 /// SHAPES ///
@@ -187,21 +288,21 @@ Hit opSmoothUnion(Hit h1, Hit h2, float k) {
     float h = clamp(0.5 + 0.5 * (h2.d - h1.d) / k, 0.0, 1.0);
     float d = mix(h2.d, h1.d, h) - k * h * (1.0 - h);
     // vec3 color = mix(h2.color, h1.color, h);
-    return Hit(d);
+    return Hit(d, h1.d < h2.d ? h1.id : h2.id);
 }  // working
 
 Hit opSmoothSubtraction(Hit h1, Hit h2, float k) {
     float h = clamp(0.5 - 0.5 * (h2.d + h1.d) / k, 0.0, 1.0);
     float d = mix(h2.d, -h1.d, h) + k * h * (1.0 - h);
     // vec3 color = mix(h2.color, h1.color, h);
-    return Hit(d);
+    return Hit(d, -h1.d > h2.d ? h1.id : h2.id);
 } // working
 
 Hit opSmoothIntersection(Hit h1, Hit h2, float k) {
     float h = clamp(0.5 - 0.5 * (h2.d - h1.d) / k, 0.0, 1.0);
     float d = mix(h2.d, h1.d, h) + k * h * (1.0 - h);
     // vec3 color = mix(h2.color, h1.color, h);
-    return Hit(d);
+    return Hit(d, h1.d > h2.d ? h1.id : h2.id);
 }  // working needs to be set before use
 
 Hit opUnion(Hit h1, Hit h2) {
@@ -209,7 +310,7 @@ Hit opUnion(Hit h1, Hit h2) {
 } // working
 
 Hit opSubtraction(Hit h1, Hit h2) {
-    return -h1.d > h2.d ? Hit(-h1.d /* mat */) : h2;
+    return -h1.d > h2.d ? Hit(-h1.d, h1.id) : h2;
 } // working needs to be set before use
 
 Hit opIntersection(Hit h1, Hit h2) {
@@ -219,7 +320,7 @@ Hit opIntersection(Hit h1, Hit h2) {
 Hit opXor(Hit h1, Hit h2) {
     float d = max(min(h1.d, h2.d), -max(h1.d, h2.d));
     // vec3 color = mix(h1.color, h2.color, 0.5);
-    return Hit(d);
+    return Hit(d, h1.d < h2.d ? h1.id : h2.id);
 }  // working
 
 
@@ -299,40 +400,169 @@ bool bool_hit(vec2 intersect) {
 
 
 
+// if chain instead of a switch, see above
+float shape(Object obj, vec3 p) {
+    vec3 data = vec3(obj.data_x, obj.data_y, obj.data_z);
+
+    if (obj.shape == 0) return sdSphere(p, data.x);
+    if (obj.shape == 1) return sdCube(p, data);
+    if (obj.shape == 2) return sdOctahedronExact(p, data.x);
+    if (obj.shape == 3) return sdMandelbulb(p, data.x);
+    return FP;
+}
+
 Hit map(vec3 p_in) {
+    Hit hit = Hit(FP, -1);
+
+    for (int i = 0; i < o.object_count; i++) {
+        Object obj = o.objects[i];
+
+        vec3 p = move(p_in, vec3(obj.pos_x, obj.pos_y, obj.pos_z));
+        p = rot3D(p, vec3(obj.rot_x, obj.rot_y, obj.rot_z));
+        p /= obj.scale;
 
-    return Hit(sdSphere(p_in, 1.0));
+        float d = scale_correction(shape(obj, p), obj.scale);
+
+        hit = opUnion(hit, Hit(d, i));
+    }
+
+    return hit;
 }
 
 
-Hit cast_ray(Ray ray) {
+Hit cast_ray(Ray ray, out int steps) {
     float t = 0.0;
+    int id = -1;
+    steps = 0;
     for (int i = 0; i < s.steps_per_ray; i++) {
+        steps = i + 1;
         vec3 p = ray.ro + ray.rd * t;
         Hit hit = map(p);
         t += hit.d;
 
-        if (hit.d < MHD) break;
+        if (hit.d < MHD) { id = hit.id; break; }
         if (t > FP) break;
     }
-    return Hit(t);
+    return Hit(t, id);
+}
+
+// central differences on the sdf
+vec3 calc_normal(vec3 p) {
+    vec2 e = vec2(MHD, 0.0);
+    return normalize(vec3(
+        map(p + e.xyy).d - map(p - e.xyy).d,
+        map(p + e.yxy).d - map(p - e.yxy).d,
+        map(p + e.yyx).d - map(p - e.yyx).d
+    ));
 }
 
 
 ////////////////////
 /// Pathtraceing ///
 ////////////////////
-vec4 pathtrace(Ray ray) {
-    vec4 back;
+vec4 pathtrace(Ray ray, out Aov aov) {
+    vec4 back = vec4(0.0);
     Hit test;
+    int steps;
 
-    test = cast_ray(ray);
+    test = cast_ray(ray, steps);
     back.g = 1.0 / test.d * 0.5;
 
+    // object id for the selection outline, -1 on a miss
+    back.a = float(test.id);
+
+    aov = Aov(vec4(0.0, 0.0, 0.0, FP), 0u, test.id, uint(steps), 0u);
+    if (test.id >= 0) {
+        Object obj = o.objects[test.id];
+        aov.normal_depth = vec4(calc_normal(ray.ro + ray.rd * test.d), test.d);
+        aov.albedo = packUnorm4x8(vec4(obj.col_r, obj.col_g, obj.col_b, 1.0));
+    }
+
     return back;
 }
 
 
+//////////////
+/// Camera ///
+//////////////
+
+// uniform point on the aperture, a disk or a polygon with bokeh_blades sides
+vec2 sample_aperture() {
+    if (s.bokeh_blades < 3) {
+        float r = sqrt(rand());
+        float a = rand() * 2.0 * PI;
+        return vec2(cos(a), sin(a)) * r;
+    }
+
+    // pick one of the triangles of the polygon then a point inside it
+    float blades = float(s.bokeh_blades);
+    float blade = floor(rand() * blades);
+    float a0 = blade / blades * 2.0 * PI;
+    float a1 = (blade + 1.0) / blades * 2.0 * PI;
+
+    float u = rand();
+    float v = rand();
+    if (u + v > 1.0) { u = 1.0 - u; v = 1.0 - v; }
+
+    return vec2(cos(a0), sin(a0)) * u + vec2(cos(a1), sin(a1)) * v;
+}
+
+// uv is -aspect..aspect by -1..1, false if the pixel is outside the projection
+bool camera_ray(vec2 uv, float aspect, out Ray ray) {
+    vec3 ro = vec3(0.0);
+    vec3 rd = vec3(0.0, 0.0, 1.0);
+
+    if (s.projection == PROJECTION_ORTHOGRAPHIC) {
+        ro = vec3(uv * s.ortho_height * 0.5, 0.0);
+    }
+    else if (s.projection == PROJECTION_EQUIRECTANGULAR) {
+        float lon = uv.x / aspect * PI;
+        float lat = uv.y * PI * 0.5;
+        rd = vec3(cos(lat) * sin(lon), sin(lat), cos(lat) * cos(lon));
+    }
+    else if (s.projection == PROJECTION_FISHEYE) {
+        // equidistant, the image circle fits the height
+        float r = length(uv);
+        if (r > 1.0) { return false; }
+
+        float theta = r * radians(s.fov) * 0.5;
+        float phi = atan(uv.y, uv.x);
+        rd = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+    }
+    else {
+        rd = normalize(vec3(uv, 1.0 / tan(radians(s.fov) * 0.5)));
+    }
+
+    // thin lens, everything on the focal plane stays sharp
+    bool planar = s.projection == PROJECTION_PERSPECTIVE || s.projection == PROJECTION_ORTHOGRAPHIC;
+    if (s.aperture_radius > 0.0 && planar) {
+        vec3 focus = ro + rd * (s.focal_distance / rd.z);
+        ro += vec3(sample_aperture() * s.aperture_radius, 0.0);
+        rd = normalize(focus - ro);
+    }
+
+    vec3 rot = vec3(s.rot_x, s.rot_y, s.rot_z);
+    ray = Ray(
+        vec3(s.pos_x, s.pos_y, s.pos_z) + rotateRayDirection(ro, rot),
+        rotateRayDirection(rd, rot)
+    );
+    return true;
+}
+
+
+float luminance(vec3 color) {
+    return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+// standard error of the pixel mean relative to its brightness, black pixels count as converged
+float relative_error(vec3 mean, SampleStats st) {
+    float lum = luminance(mean);
+    float variance = max(st.lum_m2 - lum * lum, 0.0);
+    float error = sqrt(variance / float(max(st.samples, 1u)));
+    return error / max(lum, 0.001);
+}
+
+
 mat4 mix_mat(mat4 m1, mat4 m2, float k) {
     return mat4(
     mix(m1[0], m2[0], k),
@@ -345,29 +575,96 @@ mat4 mix_mat(mat4 m1, mat4 m2, float k) {
 
 void main() {
     ivec2 gl_uv = ivec2(gl_GlobalInvocationID.xy);
-    ivec2 dimentions = imageSize(read_tex);
-    if (gl_uv.x > dimentions.x || gl_uv.y > dimentions.y) { return; }// bounds check
+    ivec2 tile_size = textureSize(read_tex, 0);
+    if (gl_uv.x >= tile_size.x || gl_uv.y >= tile_size.y) { return; }// bounds check
+
+    // when tiling the texture is one tile of a bigger image, otherwise the whole image
+    ivec2 dimentions = s.image_width > 0 ? ivec2(s.image_width, s.image_height) : tile_size;
+    ivec2 image_uv = gl_uv + ivec2(s.tile_offset_x, s.tile_offset_y);
+    if (image_uv.x >= dimentions.x || image_uv.y >= dimentions.y) { return; }
 
     float aspect = float(dimentions.x) / float(dimentions.y);
 
-    vec2 uv = vec2(gl_uv.x / float(dimentions.x), gl_uv.y / float(dimentions.y));
+    vec2 uv = vec2(image_uv.x / float(dimentions.x), image_uv.y / float(dimentions.y));
     uv = uv * 2.0 - 1.0;
     uv.x *= aspect;
 
     // setup
-    Ray ray = Ray(
-    vec3(s.pos_x, s.pos_y, s.pos_z),
-    normalize(vec3(uv, s.fov))
-    );
+    rng_state = uint(image_uv.x) * 1973u + uint(image_uv.y) * 9277u + uint(s.frame) * 26699u;
+    pcg();
 
-    // Usage
-    ray.rd = rotateRayDirection(ray.rd, vec3(s.rot_x, s.rot_y, s.rot_z));
+    // the aov and stats buffers are the size of the texture
+    uint pixel = uint(gl_uv.y * tile_size.x + gl_uv.x);
+    vec4 last = texelFetch(read_tex, gl_uv, 0);
+    bool cleared = s.frame == s.last_clear_frame;
+
+    // stats from before the last clear are stale
+    bool has_stats = pixel < uint(stats.length());
+    SampleStats st = SampleStats(0.0, 0u);
+    if (has_stats && !cleared) { st = stats[pixel]; }
 
     // path traceing
+    int samples = max(s.samples_per_frame, 1);
+
+    if (s.adaptive_sampling != 0 && has_stats && st.samples >= uint(max(s.min_adaptive_samples, 1))) {
+        float error = relative_error(last.rgb, st);
+        float threshold = max(s.noise_threshold, 0.00001);
+
+        // converged pixels keep last frame, the noisier the more samples
+        if (error < threshold) {
+            imageStore(write_tex, gl_uv, last);
+            return;
+        }
+        samples = min(int(ceil(float(samples) * error / threshold)), samples * ADAPTIVE_MAX_BOOST);
+    }
 
-    vec4 trace = pathtrace(ray);
+    vec3 color = vec3(0.0);
+    float lum_m2 = 0.0;
+    float id = -1.0;
+    Aov aov = Aov(vec4(0.0, 0.0, 0.0, FP), 0u, -1, 0u, 0u);
+
+    vec3 forward = rotateRayDirection(vec3(0.0, 0.0, 1.0), vec3(s.rot_x, s.rot_y, s.rot_z));
+    bool planar = s.projection == PROJECTION_PERSPECTIVE || s.projection == PROJECTION_ORTHOGRAPHIC;
+
+    for (int i = 0; i < samples; i++) {
+        Ray ray;
+        if (!camera_ray(uv, aspect, ray)) { break; }
+
+        Aov sample_aov;
+        vec4 trace = pathtrace(ray, sample_aov);
+        color += trace.rgb;
+        lum_m2 += luminance(trace.rgb) * luminance(trace.rgb);
+        id = trace.a;
+
+        if (i == 0) {
+            aov = sample_aov;
+            // distance along the view axis instead of along the ray
+            if (planar) { aov.normal_depth.w *= dot(ray.rd, forward); }
+        }
+    }
+    color /= float(samples);
+    lum_m2 /= float(samples);
+
+    if (pixel < uint(aovs.length())) {
+        aovs[pixel] = aov;
+    }
 
 //    vec4 col = vec4(vec3(uv, 0.0) * sin(s.time), 1.0);
 
-    imageStore(write_tex, gl_uv, trace);
+    // progressive accumulation since the last clear, the id is never blended
+    if (has_stats) {
+        // weighted by sample count since pixels can take different amounts
+        float k = float(samples) / float(st.samples + uint(samples));
+        color = mix(last.rgb, color, k);
+        st.lum_m2 = mix(st.lum_m2, lum_m2, k);
+        st.samples += uint(samples);
+        stats[pixel] = st;
+    } else {
+        float accumulated = float(s.frame - s.last_clear_frame);
+        if (accumulated > 0.0) {
+            color = mix(last.rgb, color, 1.0 / (accumulated + 1.0));
+        }
+    }
+
+    imageStore(write_tex, gl_uv, vec4(color, id));
 }
\ No newline at end of file
@@ -0,0 +1,524 @@
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::iter;
+
+use egui::{ProgressBar, Ui};
+use egui_wgpu::RenderState;
+use instant::Instant;
+use wgpu::{Buffer, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Device, Extent3d, ImageCopyBuffer, ImageDataLayout, Maintain, MapMode};
+
+use crate::app::PROF;
+use crate::path_tracer::path_tracer_package::PathTracerPackage;
+use crate::path_tracer::render_utility::gpu_profiler::GpuProfiler;
+use crate::singletons::scene::{ParthtracerSettings, Scene};
+use crate::singletons::settings::{TileOrder, TiledRenderSettings};
+
+/// bytes per pixel of the ``Rgba32Float`` storage textures
+const TEXEL_SIZE: u32 = 16;
+/// finished tiles that can be waiting on their readback before rendering waits too
+const READBACK_RING_SIZE: usize = 3;
+
+/// renders images bigger than a texture can hold one tile at a time, the finished
+/// tiles are streamed to disk so the full image never has to be in memory
+pub struct TiledRenderer {
+   /// tile sized, made on the first render
+   package: Option<PathTracerPackage>,
+   /// never active, ``PathTracerPackage::render_pass`` just wants one
+   gpu_profiler: GpuProfiler,
+   job: Option<TiledJob>,
+
+   start_requested: bool,
+   /// result of the last render, shown in the ui
+   status: String,
+}
+
+struct TiledJob {
+   settings: TiledRenderSettings,
+   tile_size: u32,
+   /// starts at the setting, the frame budget can change it
+   dispatches_per_frame: u32,
+   path_set: ParthtracerSettings,
+
+   /// top left pixel of each tile in render order
+   tiles: Vec<[u32; 2]>,
+   current_tile: usize,
+   current_sample: u32,
+
+   output: PpmWriter,
+   /// finished tiles are copied into a free slot and written out once its map completes
+   readbacks: Vec<TileReadback>,
+   start: Instant,
+   /// time spent paused is left out of the elapsed time so the eta stays honest
+   paused_at: Option<Instant>,
+   paused_for: f32,
+}
+
+impl TiledJob {
+   fn elapsed(&self) -> f32 {
+      let current_pause = self.paused_at.map_or(0.0, |p| p.elapsed().as_secs_f32());
+      (self.start.elapsed().as_secs_f32() - self.paused_for - current_pause).max(0.0)
+   }
+
+   /// writes the tiles whose readback has finished mapping, never waits
+   fn write_mapped_tiles(&mut self, device: &Device) -> std::io::Result<()> {
+      device.poll(Maintain::Poll);
+
+      for slot in self.readbacks.iter_mut() {
+         let Some(mapping) = &slot.mapping else { continue; };
+         let Ok(result) = mapping.receiver.try_recv() else { continue; };
+         let tile = mapping.tile;
+         slot.mapping = None;
+
+         result.map_err(|_| std::io::Error::other("failed to read the tile back"))?;
+
+         let data = slot.buffer.slice(..).get_mapped_range();
+         let written = self.output.write_tile(tile, self.tile_size, bytemuck::cast_slice(&data));
+         drop(data);
+         slot.buffer.unmap();
+         written?;
+      }
+
+      Ok(())
+   }
+
+   fn free_readback(&self) -> Option<usize> {
+      self.readbacks.iter().position(|slot| slot.mapping.is_none())
+   }
+}
+
+struct TileReadback {
+   buffer: Buffer,
+   mapping: Option<TileMapping>,
+}
+
+struct TileMapping {
+   /// top left pixel of the tile in the buffer
+   tile: [u32; 2],
+   receiver: flume::Receiver<Result<(), BufferAsyncError>>,
+}
+
+/// how far along the running render is
+#[derive(Copy, Clone)]
+pub struct TiledProgress {
+   pub fraction: f32,
+   pub tile: usize,
+   pub tiles: usize,
+   /// seconds, not counting pauses
+   pub elapsed: f32,
+   pub remaining: f32,
+   /// pixel samples per second so far, zero until the first tile is in
+   pub rate: f64,
+}
+
+/// what happened to a render that stopped on its own
+pub enum TiledEvent {
+   Finished { path: String, seconds: f32 },
+   Failed(String),
+}
+
+impl TiledRenderer {
+   pub fn new(render_state: &RenderState) -> Self {
+      let mut gpu_profiler = GpuProfiler::new(&render_state.device, 2);
+      gpu_profiler.active = false;
+
+      Self {
+         package: None,
+         gpu_profiler,
+         job: None,
+         start_requested: false,
+         status: String::new(),
+      }
+   }
+
+   pub fn is_rendering(&self) -> bool {
+      self.job.is_some()
+   }
+
+   /// has work to do next frame
+   pub fn is_busy(&self) -> bool {
+      self.start_requested || (self.is_rendering() && !self.is_paused())
+   }
+
+   /// dispatches per frame of the running render
+   pub fn dispatches_per_frame(&mut self) -> Option<&mut u32> {
+      self.job.as_mut().map(|job| &mut job.dispatches_per_frame)
+   }
+
+   pub fn is_paused(&self) -> bool {
+      self.job.as_ref().is_some_and(|job| job.paused_at.is_some())
+   }
+
+   pub fn set_paused(&mut self, paused: bool) {
+      let Some(job) = &mut self.job else { return; };
+
+      match (job.paused_at, paused) {
+         (None, true) => job.paused_at = Some(Instant::now()),
+         (Some(at), false) => {
+            job.paused_for += at.elapsed().as_secs_f32();
+            job.paused_at = None;
+         }
+         _ => {}
+      }
+   }
+
+   /// drops the running render, the partial file is left as is
+   pub fn cancel(&mut self) {
+      self.job = None;
+   }
+
+   pub fn progress(&self) -> Option<TiledProgress> {
+      let job = self.job.as_ref()?;
+
+      let spp = job.settings.samples_per_pixel.max(1) as f32;
+      let total = job.tiles.len() as f32 * spp;
+      let done = job.current_tile as f32 * spp + job.current_sample as f32;
+      let fraction = done / total;
+
+      let elapsed = job.elapsed();
+      let remaining = if fraction > 0.0 { elapsed / fraction - elapsed } else { 0.0 };
+      let pixel_samples = job.settings.width as f64 * job.settings.height as f64 * spp as f64;
+      let rate = if elapsed > 0.0 { pixel_samples * fraction as f64 / elapsed as f64 } else { 0.0 };
+
+      Some(TiledProgress {
+         fraction,
+         // the last tile stays up while its readback finishes
+         tile: job.current_tile.min(job.tiles.len() - 1),
+         tiles: job.tiles.len(),
+         elapsed,
+         remaining,
+         rate,
+      })
+   }
+
+   /// starts a render from the viewport panel if asked for and keeps the running one going
+   #[triglyceride::time_event(PROF, "TILED_RENDER_UPDATE")]
+   pub fn update(&mut self, render_state: &RenderState, settings: &TiledRenderSettings, scene: &Scene) {
+      if self.start_requested {
+         self.start_requested = false;
+         if let Err(e) = self.start(render_state, settings, scene) {
+            self.status = format!("Couldn't start the render: {e}");
+         }
+      }
+
+      match self.render(render_state) {
+         Some(TiledEvent::Finished { path, seconds }) => self.status = format!("Saved {path} in {seconds:.1}s"),
+         Some(TiledEvent::Failed(e)) => self.status = format!("Render failed: {e}"),
+         None => {}
+      }
+   }
+
+   /// does this frames dispatches of the running render, returns an event once it stops
+   pub fn render(&mut self, render_state: &RenderState) -> Option<TiledEvent> {
+      let (Some(job), Some(package)) = (&mut self.job, &mut self.package) else { return None; };
+
+      if let Err(e) = job.write_mapped_tiles(&render_state.device) {
+         self.job = None;
+         return Some(TiledEvent::Failed(e.to_string()));
+      }
+
+      // every tile is rendered, done once the last readbacks are written
+      if job.current_tile >= job.tiles.len() {
+         if job.readbacks.iter().all(|slot| slot.mapping.is_none()) {
+            let event = TiledEvent::Finished {
+               path: job.settings.output_path.clone(),
+               seconds: job.elapsed(),
+            };
+            self.job = None;
+            return Some(event);
+         }
+         return None;
+      }
+
+      if job.paused_at.is_some() { return None; }
+
+      for _ in 0..job.dispatches_per_frame.max(1) {
+         let tile = job.tiles[job.current_tile];
+
+         let tile_done = job.current_sample + 1 >= job.settings.samples_per_pixel.max(1);
+         // the last sample of a tile needs somewhere to copy it to, waits for a readback otherwise
+         let slot = job.free_readback();
+         if tile_done && slot.is_none() { break; }
+
+         let mut path_set = job.path_set;
+         path_set.frame = job.current_sample as i32;
+         path_set.last_clear_frame = 0;
+         path_set.tile_offset = [tile[0] as i32, tile[1] as i32];
+         package.update(render_state, path_set);
+
+         let mut encoder = render_state.device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Tiled Render Encoder"),
+         });
+         package.render_pass(&mut encoder, &mut self.gpu_profiler);
+
+         job.current_sample += 1;
+
+         if let (true, Some(slot)) = (tile_done, slot) {
+            let extent = Extent3d { width: job.tile_size, height: job.tile_size, depth_or_array_layers: 1 };
+            encoder.copy_texture_to_buffer(
+               package.storage_textures.textures.item_one().texture.as_image_copy(),
+               ImageCopyBuffer {
+                  buffer: &job.readbacks[slot].buffer,
+                  layout: ImageDataLayout {
+                     offset: 0,
+                     bytes_per_row: Some(job.tile_size * TEXEL_SIZE),
+                     rows_per_image: Some(job.tile_size),
+                  },
+               },
+               extent,
+            );
+         }
+
+         render_state.queue.submit(iter::once(encoder.finish()));
+
+         let (true, Some(slot)) = (tile_done, slot) else { continue; };
+
+         let readback = &mut job.readbacks[slot];
+         let (sender, receiver) = flume::bounded(1);
+         readback.buffer.slice(..).map_async(MapMode::Read, move |v| { let _ = sender.send(v); });
+         readback.mapping = Some(TileMapping { tile, receiver });
+
+         job.current_sample = 0;
+         job.current_tile += 1;
+
+         if job.current_tile >= job.tiles.len() { break; }
+      }
+
+      None
+   }
+
+   /// picks up a recompiled shader, a running render uses it from its next dispatch
+   pub fn remake_pipeline(&mut self, device: &Device) {
+      if let Some(package) = &mut self.package {
+         package.remake_pipeline(device);
+      }
+   }
+
+   pub fn start(&mut self, render_state: &RenderState, settings: &TiledRenderSettings, scene: &Scene) -> std::io::Result<()> {
+      let device = &render_state.device;
+
+      // multiple of 16 so a row is 256 byte aligned for the copy
+      let max_size = device.limits().max_texture_dimension_2d;
+      let tile_size = (settings.tile_size.min(max_size) / 16).max(1) * 16;
+
+      let output = PpmWriter::create(&settings.output_path, settings.width, settings.height)?;
+
+      let package = self.package.get_or_insert_with(|| PathTracerPackage::new(render_state, scene));
+      package.storage_textures.size.width = tile_size;
+      package.storage_textures.size.height = tile_size;
+      package.storage_textures.update(device);
+      package.update_objects(render_state, scene);
+
+      let readbacks = (0..READBACK_RING_SIZE).map(|_| TileReadback {
+         buffer: device.create_buffer(&BufferDescriptor {
+            label: Some("tiled_render_readback"),
+            size: tile_size as u64 * tile_size as u64 * TEXEL_SIZE as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+         }),
+         mapping: None,
+      }).collect();
+
+      let cols = settings.width.div_ceil(tile_size);
+      let rows = settings.height.div_ceil(tile_size);
+      let tiles = tile_order(settings.order, cols, rows)
+          .into_iter()
+          .map(|[x, y]| [x * tile_size, y * tile_size])
+          .collect();
+
+      let mut path_set = scene.parthtrace_settings;
+      path_set.samples_per_frame = 1;
+      path_set.image_size = [settings.width as i32, settings.height as i32];
+
+      self.job = Some(TiledJob {
+         settings: settings.clone(),
+         tile_size,
+         dispatches_per_frame: settings.dispatches_per_frame,
+         path_set,
+         tiles,
+         current_tile: 0,
+         current_sample: 0,
+         output,
+         readbacks,
+         start: Instant::now(),
+         paused_at: None,
+         paused_for: 0.0,
+      });
+      self.status.clear();
+
+      Ok(())
+   }
+
+   pub fn ui(&mut self, ui: &mut Ui, settings: &mut TiledRenderSettings) {
+      ui.group(|ui| {
+         ui.label("Tiled render");
+
+         ui.add_enabled_ui(!self.is_rendering(), |ui| {
+            settings.ui(ui);
+         });
+
+         // there's no file system in the browser to stream the tiles to
+         if cfg!(target_arch = "wasm32") {
+            ui.label("Only available on desktop");
+            return;
+         }
+
+         match self.progress() {
+            None => {
+               if ui.button("Render").clicked() {
+                  self.start_requested = true;
+               }
+            }
+            Some(progress) => {
+               ui.add(ProgressBar::new(progress.fraction).text(format!(
+                  "Tile {}/{}, {:.0}s left",
+                  progress.tile + 1,
+                  progress.tiles,
+                  progress.remaining,
+               )));
+
+               ui.horizontal(|ui| {
+                  let paused = self.is_paused();
+                  if ui.button(if paused { "Resume" } else { "Pause" }).clicked() {
+                     self.set_paused(!paused);
+                  }
+
+                  if ui.button("Cancel").clicked() {
+                     self.cancel();
+                     self.status = "Cancelled".to_string();
+                  }
+               });
+            }
+         }
+
+         if !self.status.is_empty() {
+            ui.label(&self.status);
+         }
+      });
+   }
+}
+
+
+////////////////
+// Tile order //
+////////////////
+
+/// tile coordinates (not pixels) of a ``cols`` x ``rows`` grid in render order
+pub fn tile_order(order: TileOrder, cols: u32, rows: u32) -> Vec<[u32; 2]> {
+   let scanline = (0..rows).flat_map(|y| (0..cols).map(move |x| [x, y]));
+
+   match order {
+      TileOrder::Scanline => scanline.collect(),
+      TileOrder::Spiral => spiral(cols, rows),
+      TileOrder::Hilbert => {
+         let n = cols.max(rows).next_power_of_two();
+         let mut tiles = scanline.collect::<Vec<_>>();
+         tiles.sort_by_key(|t| hilbert_index(n, t[0], t[1]));
+         tiles
+      }
+   }
+}
+
+/// walks out from the center right, down, left, up with growing arms, skipping tiles off the grid
+fn spiral(cols: u32, rows: u32) -> Vec<[u32; 2]> {
+   let total = (cols * rows) as usize;
+   let mut out = Vec::with_capacity(total);
+
+   let (mut x, mut y) = ((cols as i64 - 1) / 2, (rows as i64 - 1) / 2);
+   let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+   let mut arm = 1;
+   let mut direction = 0;
+
+   while out.len() < total {
+      // every two turns the arm gets one longer
+      for _ in 0..2 {
+         let (dx, dy) = directions[direction % 4];
+         for _ in 0..arm {
+            if x >= 0 && y >= 0 && x < cols as i64 && y < rows as i64 {
+               out.push([x as u32, y as u32]);
+            }
+            x += dx;
+            y += dy;
+         }
+         direction += 1;
+      }
+      arm += 1;
+   }
+
+   out
+}
+
+/// distance along a hilbert curve filling an ``n`` x ``n`` grid, ``n`` is a power of two
+fn hilbert_index(n: u32, mut x: u32, mut y: u32) -> u64 {
+   let mut d = 0;
+   let mut s = n / 2;
+
+   while s > 0 {
+      let rx = ((x & s) > 0) as u32;
+      let ry = ((y & s) > 0) as u32;
+      d += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+
+      // rotate the quadrant so the curve stays connected
+      if ry == 0 {
+         if rx == 1 {
+            x = n - 1 - x;
+            y = n - 1 - y;
+         }
+         std::mem::swap(&mut x, &mut y);
+      }
+      s /= 2;
+   }
+
+   d
+}
+
+
+////////////
+// Output //
+////////////
+
+/// binary ppm, rows are written straight into place so tiles can finish in any order
+struct PpmWriter {
+   file: File,
+   width: u32,
+   height: u32,
+   header_len: u64,
+}
+
+impl PpmWriter {
+   fn create(path: &str, width: u32, height: u32) -> std::io::Result<Self> {
+      let mut file = File::create(path)?;
+
+      let header = format!("P6\n{width} {height}\n255\n");
+      file.write_all(header.as_bytes())?;
+
+      let header_len = header.len() as u64;
+      file.set_len(header_len + width as u64 * height as u64 * 3)?;
+
+      Ok(Self { file, width, height, header_len })
+   }
+
+   /// ``texels`` is a whole ``tile_size`` square of rgba floats, the parts past the image edge are dropped
+   fn write_tile(&mut self, origin: [u32; 2], tile_size: u32, texels: &[f32]) -> std::io::Result<()> {
+      let row_width = tile_size.min(self.width - origin[0]) as usize;
+      let row_count = tile_size.min(self.height - origin[1]);
+
+      let mut row = Vec::with_capacity(row_width * 3);
+      for y in 0..row_count {
+         let start = (y * tile_size) as usize * 4;
+
+         row.clear();
+         for texel in texels[start..start + row_width * 4].chunks_exact(4) {
+            // same as the viewport, the display pass writes the values straight out
+            row.extend(texel[..3].iter().map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8));
+         }
+
+         // texture row 0 is the bottom of the image and ppm starts at the top
+         let file_row = self.height - 1 - (origin[1] + y);
+         let pixel = file_row as u64 * self.width as u64 + origin[0] as u64;
+         self.file.seek(SeekFrom::Start(self.header_len + pixel * 3))?;
+         self.file.write_all(&row)?;
+      }
+
+      Ok(())
+   }
+}
@@ -0,0 +1,60 @@
+use eframe::{CreationContext, Storage};
+use wgpu::Features;
+
+use crate::path_tracer::render_utility::adapter_selection::{launch_settings, save_settings, stored_settings, Adapters};
+use crate::singletons::scene_history::SceneHistory;
+use crate::singletons::settings::Settings;
+use crate::singletons::time_package::TimePackage;
+use crate::singletons::trace_recorder::TraceRecorder;
+
+/// app wide state, owned by ``MgsApp`` and handed down to whatever needs it,
+/// nothing is global so several renderers can live in one process
+pub struct AppContext {
+   pub settings: Settings,
+   pub time: TimePackage,
+   pub trace: TraceRecorder,
+   pub history: SceneHistory,
+   /// what the device was made with, optional ones the adapter lacks are missing
+   pub device_features: Features,
+   pub adapters: Adapters,
+}
+
+impl AppContext {
+   pub fn new(cc: &CreationContext) -> Self {
+      let mut settings = Settings::new(cc);
+      // a restart passes what it was started with, the saved ones may be a frame behind
+      settings.adapter_settings = launch_settings(stored_settings());
+
+      Self {
+         adapters: Adapters::new(cc.wgpu_render_state.as_ref(), settings.adapter_settings.clone()),
+         settings,
+         time: TimePackage::default(),
+         trace: TraceRecorder::default(),
+         history: SceneHistory::default(),
+         device_features: cc.wgpu_render_state.as_ref().map_or(Features::empty(), |rs| rs.device.features()),
+      }
+   }
+
+   /// once per frame before anything else reads from it
+   pub fn update(&mut self) {
+      self.time.update();
+   }
+
+   pub fn save(&self, storage: &mut dyn Storage) {
+      self.settings.save(storage);
+      save_settings(&self.settings.adapter_settings);
+   }
+}
+
+impl Default for AppContext {
+   fn default() -> Self {
+      Self {
+         settings: Settings::default(),
+         time: TimePackage::default(),
+         trace: TraceRecorder::default(),
+         history: SceneHistory::default(),
+         device_features: Features::empty(),
+         adapters: Adapters::default(),
+      }
+   }
+}
@@ -1,10 +1,18 @@
 use bytemuck::{Pod, Zeroable};
-use eframe::egui::{CollapsingHeader, DragValue, Ui};
+use eframe::egui::{CollapsingHeader, DragValue, TextEdit, Ui};
+use strum::EnumIter;
+
+use crate::user_interface::ui_modules::enum_combination_box;
 
 /// used to hold all data for the node-graph and raymarching
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(default)]
 pub struct Scene {
    pub local_shapes: Vec<ShapeEntry>,
+   pub objects: Vec<SceneObject>,
+   pub cameras: Vec<Camera>,
+   /// last camera switched to
+   pub active_camera: Option<usize>,
    pub active_cubemap: (),
    pub map_data: (),
 
@@ -14,6 +22,12 @@ impl Default for Scene {
    fn default() -> Self {
       Self {
          local_shapes: vec![],
+         objects: vec![SceneObject::default()],
+         cameras: vec![Camera {
+            name: "Main".to_string(),
+            view: CameraView::from_settings(&ParthtracerSettings::default()),
+         }],
+         active_camera: Some(0),
          active_cubemap: (),
          map_data: (),
          parthtrace_settings: ParthtracerSettings::default(),
@@ -25,7 +39,7 @@ impl Default for Scene {
 ///////////////////
 // Shape storage //
 ///////////////////
-#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
 pub struct ShapeEntry {
    pub name: String,
    pub shader_code: String,
@@ -47,12 +61,281 @@ impl ShapeEntry {
 }
 
 
+///////////////////
+// Scene objects //
+///////////////////
+/// max objects the shader can hold, has to match ``MAX_OBJECTS`` in the shader
+pub const MAX_OBJECTS: usize = 32;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct SceneObject {
+   pub name: String,
+   pub shape: ShapeType,
+   /// shape parameters, sphere -> x radius, cube -> xyz half size,
+   /// octahedron -> x size, mandelbulb -> x power
+   pub data: [f32; 3],
+   pub transform: Transform,
+   pub color: [f32; 3],
+}
+impl Default for SceneObject {
+   fn default() -> Self {
+      Self {
+         name: "sphere".to_string(),
+         shape: ShapeType::Sphere,
+         data: [1.0, 1.0, 1.0],
+         transform: Transform::default(),
+         color: [0.8, 0.8, 0.8],
+      }
+   }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, EnumIter, Debug, PartialEq)]
+pub enum ShapeType {
+   Sphere,
+   Cube,
+   Octahedron,
+   Mandelbulb,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, PartialEq)]
+#[serde(default)]
+pub struct Transform {
+   pub position: [f32; 3],
+   /// euler angles in radians, applied x -> y -> z
+   pub rotation: [f32; 3],
+   pub scale: f32,
+}
+impl Default for Transform {
+   fn default() -> Self {
+      Self {
+         position: [0.0, 0.0, 0.0],
+         rotation: [0.0, 0.0, 0.0],
+         scale: 1.0,
+      }
+   }
+}
+
+/// gpu layout of a ``SceneObject``, all scalars so std140 doesn't pad it
+#[repr(C)]
+#[derive(Pod, Copy, Clone, Zeroable)]
+pub struct GpuObject {
+   pub shape: i32,
+   pub data: [f32; 3],
+   pub position: [f32; 3],
+   pub rotation: [f32; 3],
+   pub scale: f32,
+   pub color: [f32; 3],
+   _padding: [f32; 2],
+}
+impl GpuObject {
+   pub fn from_object(object: &SceneObject) -> Self {
+      Self {
+         shape: object.shape as i32,
+         data: object.data,
+         position: object.transform.position,
+         rotation: object.transform.rotation,
+         scale: object.transform.scale,
+         color: object.color,
+         _padding: [0.0; 2],
+      }
+   }
+}
+
+#[repr(C)]
+#[derive(Pod, Copy, Clone, Zeroable)]
+pub struct SceneObjectsUniform {
+   pub count: i32,
+   _padding: [i32; 3],
+   pub objects: [GpuObject; MAX_OBJECTS],
+}
+impl SceneObjectsUniform {
+   pub fn from_scene(scene: &Scene) -> Self {
+      let mut out = Self::zeroed();
+      for (i, object) in scene.objects.iter().take(MAX_OBJECTS).enumerate() {
+         out.objects[i] = GpuObject::from_object(object);
+      }
+      out.count = scene.objects.len().min(MAX_OBJECTS) as i32;
+      out
+   }
+}
+
+
+/////////////
+// Cameras //
+/////////////
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub struct Camera {
+   pub name: String,
+   pub view: CameraView,
+}
+
+/// the camera part of ``ParthtracerSettings``
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, PartialEq)]
+#[serde(default)]
+pub struct CameraView {
+   pub position: [f32; 3],
+   pub rotation: [f32; 3],
+   pub projection: Projection,
+   /// saved under a new name, ``fov`` used to be a ray length and not degrees
+   #[serde(rename = "fov_degrees")]
+   pub fov: f32,
+   pub ortho_height: f32,
+
+   pub aperture_radius: f32,
+   pub focal_distance: f32,
+   pub bokeh_blades: i32,
+}
+impl Default for CameraView {
+   fn default() -> Self {
+      Self::from_settings(&ParthtracerSettings::default())
+   }
+}
+impl CameraView {
+   pub fn from_settings(settings: &ParthtracerSettings) -> Self {
+      Self {
+         position: settings.camera_pos,
+         rotation: settings.camera_dir,
+         projection: settings.projection(),
+         fov: settings.fov,
+         ortho_height: settings.ortho_height,
+         aperture_radius: settings.aperture_radius,
+         focal_distance: settings.focal_distance,
+         bokeh_blades: settings.bokeh_blades,
+      }
+   }
+
+   pub fn apply(&self, settings: &mut ParthtracerSettings) {
+      settings.camera_pos = self.position;
+      settings.camera_dir = self.rotation;
+      settings.projection = self.projection as i32;
+      settings.fov = self.fov;
+      settings.ortho_height = self.ortho_height;
+      settings.aperture_radius = self.aperture_radius;
+      settings.focal_distance = self.focal_distance;
+      settings.bokeh_blades = self.bokeh_blades;
+   }
+
+   /// ``t`` of 0 is ``self``, 1 is ``other``, angles take the short way round
+   pub fn lerp(&self, other: &CameraView, t: f32) -> Self {
+      let lerp = |a: f32, b: f32| a + (b - a) * t;
+      let lerp_angle = |a: f32, b: f32| {
+         let diff = (b - a + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+         a + diff * t
+      };
+
+      Self {
+         position: [0, 1, 2].map(|i| lerp(self.position[i], other.position[i])),
+         rotation: [0, 1, 2].map(|i| lerp_angle(self.rotation[i], other.rotation[i])),
+         projection: if t < 1.0 { self.projection } else { other.projection },
+         fov: lerp(self.fov, other.fov),
+         ortho_height: lerp(self.ortho_height, other.ortho_height),
+         aperture_radius: lerp(self.aperture_radius, other.aperture_radius),
+         focal_distance: lerp(self.focal_distance, other.focal_distance),
+         bokeh_blades: if t < 1.0 { self.bokeh_blades } else { other.bokeh_blades },
+      }
+   }
+
+   /// projection and lens editor, returns true if anything changed
+   pub fn ui(&mut self, ui: &mut Ui) -> bool {
+      let mut changed = enum_combination_box(ui, &mut self.projection, "Projection");
+
+      ui.horizontal(|ui| {
+         changed |= match self.projection {
+            Projection::Perspective => {
+               ui.add(DragValue::new(&mut self.fov).range(1.0..=179.0).speed(0.1).prefix("FOV: ").suffix("°")).changed()
+            }
+            Projection::Orthographic => {
+               ui.add(DragValue::new(&mut self.ortho_height).range(0.01..=200.0).speed(0.01).prefix("View height: ")).changed()
+            }
+            Projection::Equirectangular => {
+               ui.label("Full 360° x 180°");
+               false
+            }
+            Projection::Fisheye => {
+               ui.add(DragValue::new(&mut self.fov).range(1.0..=360.0).speed(0.1).prefix("FOV: ").suffix("°")).changed()
+            }
+         };
+      });
+
+      ui.horizontal(|ui| {
+         changed |= ui.add(DragValue::new(&mut self.aperture_radius).range(0.0..=2.0).speed(0.001).prefix("Aperture: ")).changed();
+         changed |= ui.add(DragValue::new(&mut self.focal_distance).range(0.01..=200.0).speed(0.01).prefix("Focus: ")).changed();
+         changed |= ui.add(DragValue::new(&mut self.bokeh_blades).range(0..=12).speed(0.05).prefix("Blades: ")).changed();
+      });
+
+      changed
+   }
+}
+
+impl Scene {
+   /// bookmarks the current view as a new camera
+   pub fn save_current_view(&mut self) {
+      self.cameras.push(Camera {
+         name: format!("View {}", self.cameras.len() + 1),
+         view: CameraView::from_settings(&self.parthtrace_settings),
+      });
+      self.active_camera = Some(self.cameras.len() - 1);
+   }
+
+   /// camera list editor, returns the camera to switch to
+   pub fn cameras_ui(&mut self, ui: &mut Ui) -> Option<usize> {
+      let mut switch_to = None;
+      let mut remove = None;
+
+      ui.group(|ui| {
+         ui.horizontal(|ui| {
+            ui.label("Cameras");
+            if ui.button("Save current view").clicked() {
+               self.save_current_view();
+            }
+         });
+
+         for (i, camera) in self.cameras.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+               if ui.selectable_label(self.active_camera == Some(i), "▶").on_hover_text("Switch to").clicked() {
+                  switch_to = Some(i);
+               }
+
+               ui.add(TextEdit::singleline(&mut camera.name).desired_width(100.0));
+
+               if ui.button("Update").on_hover_text("Overwrite with the current view").clicked() {
+                  camera.view = CameraView::from_settings(&self.parthtrace_settings);
+               }
+
+               if ui.button("🗑").clicked() {
+                  remove = Some(i);
+               }
+            });
+
+            CollapsingHeader::new("Projection and lens")
+                .id_source(i)
+                .show(ui, |ui| {
+                   // the active camera is the view, edits to it show right away
+                   if camera.view.ui(ui) && self.active_camera == Some(i) {
+                      camera.view.apply(&mut self.parthtrace_settings);
+                   }
+                });
+         }
+      });
+
+      if let Some(i) = remove {
+         self.cameras.remove(i);
+         self.active_camera = None;
+      }
+
+      switch_to
+   }
+}
+
+
 /////////////////////////
 // Pathtracer settings //
 /////////////////////////
 #[repr(C)]
 #[derive(Pod, Copy, Clone, Zeroable)]
 #[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct ParthtracerSettings {
    pub time: f32,
    pub frame: i32,
@@ -62,11 +345,45 @@ pub struct ParthtracerSettings {
    pub steps_per_ray: i32,
    pub bounces: i32,
 
+   /// a ``Projection``
+   pub projection: i32,
+   /// vertical fov in degrees, the full image circle for fisheye,
+   /// saved under a new name, ``fov`` used to be a ray length and not degrees
+   #[serde(rename = "fov_degrees")]
    pub fov: f32,
+   /// orthographic view height in world units
+   pub ortho_height: f32,
 
    pub camera_pos: [f32; 3],
    pub camera_dir: [f32; 3],
+
+   /// thin lens, a radius of 0 is a pinhole camera
+   pub aperture_radius: f32,
+   pub focal_distance: f32,
+   /// bokeh shape, less than 3 is round
+   pub bokeh_blades: i32,
+
+   /// 1 stops sampling converged pixels and gives noisy ones more
+   pub adaptive_sampling: i32,
+   /// relative standard error of a pixel's luminance under which it counts as converged
+   pub noise_threshold: f32,
+   /// samples a pixel needs before its noise estimate is trusted
+   pub min_adaptive_samples: i32,
+
+   /// set by the tiled renderer, where the current tile sits in the full image
+   pub tile_offset: [i32; 2],
+   /// full image size when tiling, 0 uses the texture size
+   pub image_size: [i32; 2],
 }
+/// has to match the ``PROJECTION_`` defines in the shader
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, EnumIter, Debug, PartialEq)]
+pub enum Projection {
+   Perspective = 0,
+   Orthographic = 1,
+   Equirectangular = 2,
+   Fisheye = 3,
+}
+
 impl Default for ParthtracerSettings {
    fn default() -> Self {
       Self {
@@ -76,13 +393,32 @@ impl Default for ParthtracerSettings {
          samples_per_frame: 0,
          steps_per_ray: 80,
          bounces: 8,
-         fov: 1.0,
+         projection: Projection::Perspective as i32,
+         fov: 90.0,
+         ortho_height: 5.0,
          camera_pos: [0.0, 0.0, 0.0],
          camera_dir: [0.0, 0.0, 0.0],
+         aperture_radius: 0.0,
+         focal_distance: 5.0,
+         bokeh_blades: 0,
+         adaptive_sampling: 0,
+         noise_threshold: 0.02,
+         min_adaptive_samples: 16,
+         tile_offset: [0, 0],
+         image_size: [0, 0],
       }
    }
 }
 impl ParthtracerSettings {
+   pub fn projection(&self) -> Projection {
+      match self.projection {
+         1 => Projection::Orthographic,
+         2 => Projection::Equirectangular,
+         3 => Projection::Fisheye,
+         _ => Projection::Perspective,
+      }
+   }
+
    pub fn ui(&mut self, ui: &mut Ui) {
       ui.group(|ui| {
          CollapsingHeader::new("Variables").show(ui, |ui| {
@@ -98,9 +434,43 @@ impl ParthtracerSettings {
                ui.add(DragValue::new(&mut self.steps_per_ray).range(1..=320).speed(0.1).prefix("Steps: "));
                ui.add(DragValue::new(&mut self.bounces).range(0..=32).speed(0.1).prefix("Bounces: "));
             });
+
+            let mut adaptive = self.adaptive_sampling != 0;
+            if ui.checkbox(&mut adaptive, "Adaptive sampling").changed() {
+               self.adaptive_sampling = adaptive as i32;
+            }
+            ui.add_enabled_ui(adaptive, |ui| {
+               ui.horizontal(|ui| {
+                  ui.add(DragValue::new(&mut self.noise_threshold).range(0.001..=1.0).speed(0.001).prefix("Noise threshold: "));
+                  ui.add(DragValue::new(&mut self.min_adaptive_samples).range(1..=1024).speed(0.1).prefix("Min samples: "));
+               });
+            });
+         });
+
+         ui.group(|ui| {
+            ui.label("Projection");
+
+            let mut projection = self.projection();
+            if enum_combination_box(ui, &mut projection, "Type") {
+               self.projection = projection as i32;
+            }
+
             ui.horizontal(|ui| {
-               ui.add(DragValue::new(&mut self.fov).range(0.0..=10.0).speed(0.001).prefix("FOV"));
-            })
+               match projection {
+                  Projection::Perspective => {
+                     ui.add(DragValue::new(&mut self.fov).range(1.0..=179.0).speed(0.1).prefix("FOV: ").suffix("°"));
+                  }
+                  Projection::Orthographic => {
+                     ui.add(DragValue::new(&mut self.ortho_height).range(0.01..=200.0).speed(0.01).prefix("View height: "));
+                  }
+                  Projection::Equirectangular => {
+                     ui.label("Full 360° x 180°");
+                  }
+                  Projection::Fisheye => {
+                     ui.add(DragValue::new(&mut self.fov).range(1.0..=360.0).speed(0.1).prefix("FOV: ").suffix("°"));
+                  }
+               }
+            });
          });
 
          ui.group(|ui| {
@@ -112,6 +482,15 @@ impl ParthtracerSettings {
             });
          });
 
+         ui.group(|ui| {
+            ui.label("Lens");
+            ui.horizontal(|ui| {
+               ui.add(DragValue::new(&mut self.aperture_radius).range(0.0..=2.0).speed(0.001).prefix("Aperture: "));
+               ui.add(DragValue::new(&mut self.focal_distance).range(0.01..=200.0).speed(0.01).prefix("Focus: "));
+               ui.add(DragValue::new(&mut self.bokeh_blades).range(0..=12).speed(0.05).prefix("Blades: "));
+            });
+         });
+
       });
    }
 }
\ No newline at end of file
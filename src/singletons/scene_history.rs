@@ -0,0 +1,93 @@
+use instant::Instant;
+
+use crate::singletons::scene::Scene;
+
+/// seconds the scene has to stay the same before an edit becomes an undo step,
+/// so a drag is one step and not one per frame
+const SETTLE_SECONDS: f32 = 0.5;
+const MAX_STEPS: usize = 100;
+
+/// undo and redo of the objects, shapes and cameras, the view and render
+/// settings are left alone so looking around doesn't fill the history
+#[derive(Default)]
+pub struct SceneHistory {
+   undo: Vec<Scene>,
+   redo: Vec<Scene>,
+   /// the scene as of the last step
+   committed: Option<Scene>,
+   changed_at: Option<Instant>,
+}
+
+impl SceneHistory {
+   /// once per frame, records a step once the scene has settled after a change
+   pub fn update(&mut self, scene: &Scene) {
+      let Some(committed) = &self.committed else {
+         self.committed = Some(scene.clone());
+         return;
+      };
+
+      if tracked_eq(committed, scene) {
+         self.changed_at = None;
+         return;
+      }
+
+      let changed_at = *self.changed_at.get_or_insert_with(Instant::now);
+      if changed_at.elapsed().as_secs_f32() > SETTLE_SECONDS {
+         self.commit(scene.clone());
+         self.redo.clear();
+      }
+   }
+
+   pub fn can_undo(&self) -> bool {
+      !self.undo.is_empty() || self.changed_at.is_some()
+   }
+
+   pub fn can_redo(&self) -> bool {
+      !self.redo.is_empty()
+   }
+
+   pub fn undo(&mut self, scene: &mut Scene) {
+      // an edit that hasn't settled yet is its own step
+      if self.changed_at.is_some() {
+         self.commit(scene.clone());
+         self.redo.clear();
+      }
+
+      let Some(previous) = self.undo.pop() else { return; };
+      self.redo.push(self.committed.replace(previous.clone()).unwrap());
+      restore(scene, previous);
+   }
+
+   pub fn redo(&mut self, scene: &mut Scene) {
+      let Some(next) = self.redo.pop() else { return; };
+
+      self.undo.push(self.committed.replace(next.clone()).unwrap());
+      self.changed_at = None;
+      restore(scene, next);
+   }
+
+   fn commit(&mut self, scene: Scene) {
+      if let Some(previous) = self.committed.replace(scene) {
+         self.undo.push(previous);
+      }
+      if self.undo.len() > MAX_STEPS {
+         self.undo.remove(0);
+      }
+
+      self.changed_at = None;
+   }
+}
+
+/// whether the tracked parts of the scenes match, runs every frame so it compares in place
+fn tracked_eq(a: &Scene, b: &Scene) -> bool {
+   a.local_shapes == b.local_shapes && a.objects == b.objects && a.cameras == b.cameras
+}
+
+fn restore(scene: &mut Scene, from: Scene) {
+   scene.local_shapes = from.local_shapes;
+   scene.objects = from.objects;
+   scene.cameras = from.cameras;
+   if scene.active_camera.is_some_and(|i| i >= scene.cameras.len()) {
+      scene.active_camera = None;
+   }
+}
@@ -1,25 +1,48 @@
 use catppuccin_egui::{FRAPPE, LATTE, MACCHIATO, MOCHA};
 use eframe::{CreationContext, Storage};
-use eframe::egui::{Context, Visuals};
+use eframe::egui::{Context, DragValue, Ui, Visuals};
 use serde_json::{from_str, to_string};
 use strum::{Display, EnumIter};
 
-use crate::init_none_static;
+use crate::path_tracer::denoise_pipeline::MAX_DENOISE_ITERATIONS;
 use crate::singletons::scene::Scene;
+use crate::user_interface::keymap::Keymap;
+use crate::user_interface::theme_editor::CustomTheme;
+use crate::user_interface::ui_modules::{enum_combination_box, ToggleSwitch};
 
-init_none_static!(SETTINGS: Settings);
-
-/// global settings for the app, init in ``App::new()`` and saved in ``App::save()``
+/// settings for the app, lives in ``AppContext``, loaded in ``App::new()`` and saved in ``App::save()``
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(default)]
 pub struct Settings {
    pub theme: Theme,
+   pub custom_themes: Vec<CustomTheme>,
 
    pub saved_scenes: Vec<Scene>,
    pub current_scene: Scene,
 
    pub image_size_settings: ImageSizeSettings,
+   pub dynamic_resolution_settings: DynamicResolutionSettings,
 
    pub graph_settings: GraphSettings,
+
+   pub gizmo_settings: GizmoSettings,
+
+   pub denoise_settings: DenoiseSettings,
+
+   pub tiled_render_settings: TiledRenderSettings,
+
+   pub frame_budget_settings: FrameBudgetSettings,
+   pub frame_pacing_settings: FramePacingSettings,
+
+   /// saved to its own file by ``adapter_selection::save_settings``, it's read before eframe's storage is open
+   #[serde(skip)]
+   pub adapter_settings: AdapterSettings,
+
+   pub keymap: Keymap,
+
+   /// saved settings that failed to parse, written back under their own key so they aren't lost
+   #[serde(skip)]
+   unparsed: Option<String>,
 }
 
 impl Settings {
@@ -31,8 +54,14 @@ impl Settings {
       match per {
          None => Settings::default(),
          Some(str) => {
-            let set: Settings = from_str(str.as_str()).unwrap_or_default();
-            set.theme.set_theme(&cc.egui_ctx);
+            let set = match from_str::<Settings>(str.as_str()) {
+               Ok(set) => set,
+               Err(e) => {
+                  log::error!("couldn't load the saved settings, starting from defaults and keeping them as settings_unparsed: {e}");
+                  Settings { unparsed: Some(str), ..Settings::default() }
+               }
+            };
+            set.apply_theme(&cc.egui_ctx);
             set
          }
       }
@@ -41,6 +70,27 @@ impl Settings {
    /// # Panics
    pub fn save(&self, storage: &mut dyn Storage) {
       storage.set_string("settings", to_string(self).unwrap());
+      if let Some(unparsed) = &self.unparsed {
+         storage.set_string("settings_unparsed", unparsed.clone());
+      }
+   }
+
+   /// sets the visuals and style for ``theme``, a custom theme that no longer exists falls back to dark
+   pub fn apply_theme(&self, ctx: &Context) {
+      let custom = match self.theme {
+         Theme::Custom(i) => self.custom_themes.get(i),
+         _ => None,
+      };
+
+      match (custom, self.theme.palette()) {
+         (Some(custom), _) => catppuccin_egui::set_theme(ctx, custom.palette()),
+         (None, Some(palette)) => catppuccin_egui::set_theme(ctx, palette),
+         (None, None) if self.theme == Theme::Light => ctx.set_visuals(Visuals::light()),
+         (None, None) => ctx.set_visuals(Visuals::dark()),
+      }
+
+      let tweaks = custom.map(|c| c.style).unwrap_or_default();
+      ctx.style_mut(|style| tweaks.apply(style));
    }
 }
 
@@ -48,10 +98,20 @@ impl Default for Settings {
    fn default() -> Self {
       Self {
          theme: Theme::Dark,
+         custom_themes: vec![],
          saved_scenes: vec![],
          current_scene: Scene::default(),
          image_size_settings: ImageSizeSettings::default(),
+         dynamic_resolution_settings: DynamicResolutionSettings::default(),
          graph_settings: GraphSettings::default(),
+         gizmo_settings: GizmoSettings::default(),
+         denoise_settings: DenoiseSettings::default(),
+         tiled_render_settings: TiledRenderSettings::default(),
+         frame_budget_settings: FrameBudgetSettings::default(),
+         frame_pacing_settings: FramePacingSettings::default(),
+         adapter_settings: AdapterSettings::default(),
+         keymap: Keymap::default(),
+         unparsed: None,
       }
    }
 }
@@ -69,18 +129,20 @@ pub enum Theme {
    Frappe,
    Macchiato,
    Mocha,
+   /// index into ``Settings::custom_themes``
+   Custom(usize),
 }
 
 impl Theme {
-   pub fn set_theme(&self, ctx: &Context) {
+   /// catppuccin palette of the built in themes, none for egui's own and custom ones
+   pub fn palette(&self) -> Option<catppuccin_egui::Theme> {
       match self {
-         Theme::Dark => ctx.set_visuals(Visuals::dark()),
-         Theme::Light => ctx.set_visuals(Visuals::light()),
-         Theme::Latte => catppuccin_egui::set_theme(ctx, LATTE),
-         Theme::Frappe => catppuccin_egui::set_theme(ctx, FRAPPE),
-         Theme::Macchiato => catppuccin_egui::set_theme(ctx, MACCHIATO),
-         Theme::Mocha => catppuccin_egui::set_theme(ctx, MOCHA),
-      };
+         Theme::Latte => Some(LATTE),
+         Theme::Frappe => Some(FRAPPE),
+         Theme::Macchiato => Some(MACCHIATO),
+         Theme::Mocha => Some(MOCHA),
+         Theme::Dark | Theme::Light | Theme::Custom(_) => None,
+      }
    }
 }
 
@@ -89,6 +151,7 @@ impl Theme {
 // Image size settings //
 /////////////////////////
 #[derive(serde::Serialize, serde::Deserialize, Copy, Clone)]
+#[serde(default)]
 pub struct ImageSizeSettings {
    pub maintain_aspect_ratio: bool,
    pub selected_aspect: (i32, i32),
@@ -122,10 +185,53 @@ pub enum SamplingType {
 }
 
 
+////////////////////////
+// Dynamic resolution //
+////////////////////////
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone)]
+#[serde(default)]
+pub struct DynamicResolutionSettings {
+   pub enabled: bool,
+   /// fraction of the resolution used while things are changing
+   pub scale: f32,
+   /// seconds without changes before going back to full resolution
+   pub settle_time: f32,
+}
+
+impl Default for DynamicResolutionSettings {
+   fn default() -> Self {
+      Self {
+         enabled: false,
+         scale: 0.5,
+         settle_time: 0.2,
+      }
+   }
+}
+
+impl DynamicResolutionSettings {
+   pub fn ui(&mut self, ui: &mut Ui) {
+      ui.group(|ui| {
+         ui.horizontal(|ui| {
+            ui.add(ToggleSwitch::new(&mut self.enabled));
+            ui.label("Dynamic resolution");
+         }).response.on_hover_text("Renders at a lower resolution while the camera or scene is changing");
+
+         ui.add_enabled_ui(self.enabled, |ui| {
+            ui.horizontal(|ui| {
+               ui.add(DragValue::new(&mut self.scale).range(0.1..=1.0).speed(0.01).prefix("Scale: "));
+               ui.add(DragValue::new(&mut self.settle_time).range(0.0..=2.0).speed(0.01).prefix("Settle: ").suffix(" s"));
+            });
+         });
+      });
+   }
+}
+
+
 ////////////////////
 // Graph settings //
 ////////////////////
 #[derive(serde::Serialize, serde::Deserialize, Copy, Clone)]
+#[serde(default)]
 pub struct GraphSettings {
    pub fps_graph_settings: GeneralGraphOptions,
    pub gpu_profiler_graph_settings: GeneralGraphOptions,
@@ -154,4 +260,383 @@ pub struct GeneralGraphOptions {
    pub include_upper: f32,
    pub update_rate: f64,
    pub amount: usize,
-}
\ No newline at end of file
+}
+
+
+////////////////////
+// Gizmo settings //
+////////////////////
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone)]
+#[serde(default)]
+pub struct GizmoSettings {
+   pub mode: GizmoMode,
+   pub space: GizmoSpace,
+
+   /// holding ctrl inverts this while dragging
+   pub snapping: bool,
+   pub translate_snap: f32,
+   /// degrees
+   pub rotate_snap: f32,
+   pub scale_snap: f32,
+}
+
+impl Default for GizmoSettings {
+   fn default() -> Self {
+      Self {
+         mode: GizmoMode::Translate,
+         space: GizmoSpace::World,
+
+         snapping: false,
+         translate_snap: 0.25,
+         rotate_snap: 15.0,
+         scale_snap: 0.1,
+      }
+   }
+}
+
+impl GizmoSettings {
+   pub fn ui(&mut self, ui: &mut Ui) {
+      ui.group(|ui| {
+         ui.label("Gizmo");
+
+         enum_combination_box(ui, &mut self.mode, "Mode");
+         enum_combination_box(ui, &mut self.space, "Space");
+
+         ui.horizontal(|ui| {
+            ui.add(ToggleSwitch::new(&mut self.snapping));
+            ui.label("Snapping");
+         }).response.on_hover_text("Hold ctrl while dragging to invert");
+
+         ui.horizontal(|ui| {
+            ui.add(DragValue::new(&mut self.translate_snap).range(0.001..=10.0).speed(0.01).prefix("Move: "));
+            ui.add(DragValue::new(&mut self.rotate_snap).range(1.0..=90.0).speed(0.1).prefix("Rotate: ").suffix("°"));
+            ui.add(DragValue::new(&mut self.scale_snap).range(0.001..=10.0).speed(0.01).prefix("Scale: "));
+         });
+      });
+   }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, EnumIter, Debug, PartialEq)]
+pub enum GizmoMode {
+   Translate,
+   Rotate,
+   Scale,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, EnumIter, Debug, PartialEq)]
+pub enum GizmoSpace {
+   World,
+   Local,
+}
+
+
+//////////////////////
+// Denoise settings //
+//////////////////////
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone)]
+#[serde(default)]
+pub struct DenoiseSettings {
+   /// off shows the raw accumulated image
+   pub enabled: bool,
+   /// how different two colors can be before they stop blurring into each other
+   pub strength: f32,
+   /// each pass doubles the filter radius, capped at ``MAX_DENOISE_ITERATIONS``
+   pub iterations: u32,
+}
+
+impl Default for DenoiseSettings {
+   fn default() -> Self {
+      Self {
+         enabled: false,
+         strength: 1.0,
+         iterations: 4,
+      }
+   }
+}
+
+impl DenoiseSettings {
+   pub fn ui(&mut self, ui: &mut Ui) {
+      ui.group(|ui| {
+         ui.label("Denoiser");
+
+         ui.horizontal(|ui| {
+            ui.add(ToggleSwitch::new(&mut self.enabled));
+            ui.label(if self.enabled { "Denoised" } else { "Raw" });
+         });
+
+         ui.add_enabled_ui(self.enabled, |ui| {
+            ui.horizontal(|ui| {
+               ui.add(DragValue::new(&mut self.strength).range(0.0..=10.0).speed(0.01).prefix("Strength: "));
+               ui.add(DragValue::new(&mut self.iterations).range(1..=MAX_DENOISE_ITERATIONS).prefix("Iterations: "));
+            });
+         });
+      });
+   }
+}
+
+
+//////////////////
+// Frame budget //
+//////////////////
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone)]
+#[serde(default)]
+pub struct FrameBudgetSettings {
+   /// takes over samples per frame, and the dispatches per frame of a tiled render
+   pub enabled: bool,
+   pub target_ms: f32,
+   pub max_samples_per_frame: i32,
+}
+
+impl Default for FrameBudgetSettings {
+   fn default() -> Self {
+      Self {
+         enabled: false,
+         target_ms: 16.0,
+         max_samples_per_frame: 16,
+      }
+   }
+}
+
+impl FrameBudgetSettings {
+   pub fn ui(&mut self, ui: &mut Ui) {
+      ui.group(|ui| {
+         ui.label("Frame budget");
+
+         ui.horizontal(|ui| {
+            ui.add(ToggleSwitch::new(&mut self.enabled));
+            ui.label("Auto samples");
+         }).response.on_hover_text("Overrides samples per frame to fit the target");
+
+         ui.add_enabled_ui(self.enabled, |ui| {
+            ui.horizontal(|ui| {
+               ui.add(DragValue::new(&mut self.target_ms).range(1.0..=100.0).speed(0.1).prefix("Target: ").suffix(" ms"));
+               ui.add(DragValue::new(&mut self.max_samples_per_frame).range(1..=64).speed(0.05).prefix("Max samples: "));
+            });
+         });
+      });
+   }
+}
+
+
+//////////////////
+// Frame pacing //
+//////////////////
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone)]
+#[serde(default)]
+pub struct FramePacingSettings {
+   pub fps_cap_enabled: bool,
+   pub fps_cap: u32,
+   /// stops repainting once every pixel has ``converged_samples``, input wakes it back up
+   pub idle_when_converged: bool,
+   pub converged_samples: u32,
+}
+
+impl Default for FramePacingSettings {
+   fn default() -> Self {
+      Self {
+         fps_cap_enabled: false,
+         fps_cap: 60,
+         idle_when_converged: false,
+         converged_samples: 1024,
+      }
+   }
+}
+
+impl FramePacingSettings {
+   /// time between frames, none when uncapped
+   pub fn frame_interval(&self) -> Option<std::time::Duration> {
+      self.fps_cap_enabled.then(|| std::time::Duration::from_secs_f64(1.0 / self.fps_cap.max(1) as f64))
+   }
+
+   pub fn ui(&mut self, ui: &mut Ui) {
+      ui.horizontal(|ui| {
+         ui.add(ToggleSwitch::new(&mut self.fps_cap_enabled));
+         ui.label("Fps cap");
+         ui.add_enabled(self.fps_cap_enabled, DragValue::new(&mut self.fps_cap).range(1..=1000).suffix(" fps"));
+      });
+
+      ui.horizontal(|ui| {
+         ui.add(ToggleSwitch::new(&mut self.idle_when_converged));
+         ui.label("Idle when converged");
+         ui.add_enabled(self.idle_when_converged, DragValue::new(&mut self.converged_samples).range(1..=1_000_000).suffix(" samples"));
+      }).response.on_hover_text("Stops rendering once the image has this many samples, moving the mouse or editing wakes it up");
+   }
+}
+
+
+///////////////////
+// Tiled renders //
+///////////////////
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(default)]
+pub struct TiledRenderSettings {
+   pub width: u32,
+   pub height: u32,
+
+   /// square tiles, a multiple of 16 so rows line up for the readback copy
+   pub tile_size: u32,
+   /// one sample pass over one tile per dispatch, keeps the ui responsive
+   pub dispatches_per_frame: u32,
+   pub samples_per_pixel: u32,
+   pub order: TileOrder,
+
+   /// written as a binary ppm as the tiles finish
+   pub output_path: String,
+}
+
+impl Default for TiledRenderSettings {
+   fn default() -> Self {
+      Self {
+         width: 16000,
+         height: 9000,
+
+         tile_size: 512,
+         dispatches_per_frame: 4,
+         samples_per_pixel: 64,
+         order: TileOrder::Spiral,
+
+         output_path: "render.ppm".to_string(),
+      }
+   }
+}
+
+impl TiledRenderSettings {
+   pub fn ui(&mut self, ui: &mut Ui) {
+      ui.horizontal(|ui| {
+         ui.add(DragValue::new(&mut self.width).range(1..=65535).prefix("W: "));
+         ui.add(DragValue::new(&mut self.height).range(1..=65535).prefix("H: "));
+      });
+
+      ui.horizontal(|ui| {
+         if ui.add(DragValue::new(&mut self.tile_size).range(16..=2048).speed(16.0).prefix("Tile: ")).changed() {
+            self.tile_size = (self.tile_size / 16).max(1) * 16;
+         }
+         ui.add(DragValue::new(&mut self.dispatches_per_frame).range(1..=64).prefix("Dispatches / frame: "));
+      });
+
+      ui.add(DragValue::new(&mut self.samples_per_pixel).range(1..=65536).prefix("Samples: "));
+      enum_combination_box(ui, &mut self.order, "Tile order");
+
+      ui.horizontal(|ui| {
+         ui.label("Output");
+         ui.text_edit_singleline(&mut self.output_path);
+      });
+   }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, EnumIter, Debug, PartialEq)]
+pub enum TileOrder {
+   Scanline,
+   /// from the center out, the interesting part usually shows up first
+   Spiral,
+   /// keeps neighbouring tiles close in time
+   Hilbert,
+}
+
+
+/////////////
+// Adapter //
+/////////////
+/// read before the window opens, changes only apply after a restart
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct AdapterSettings {
+   pub backend: BackendChoice,
+   pub power_preference: PowerChoice,
+   pub present_mode: PresentChoice,
+   /// adapter picked from the list, eframe only lets us steer by backend and power
+   /// preference so this is kept to tell when it picked another one anyway
+   pub adapter_name: Option<String>,
+}
+
+impl Default for AdapterSettings {
+   fn default() -> Self {
+      Self {
+         backend: BackendChoice::Auto,
+         power_preference: PowerChoice::HighPerformance,
+         present_mode: PresentChoice::AutoNoVsync,
+         adapter_name: None,
+      }
+   }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, EnumIter, Debug, PartialEq)]
+pub enum BackendChoice {
+   /// whatever eframe would use, ``WGPU_BACKEND`` is still respected
+   Auto,
+   Vulkan,
+   Metal,
+   Dx12,
+   /// also where software rasterizers like llvmpipe usually show up
+   Gl,
+}
+
+impl BackendChoice {
+   /// none leaves eframe's default
+   pub fn backends(&self) -> Option<wgpu::Backends> {
+      match self {
+         BackendChoice::Auto => None,
+         BackendChoice::Vulkan => Some(wgpu::Backends::VULKAN),
+         BackendChoice::Metal => Some(wgpu::Backends::METAL),
+         BackendChoice::Dx12 => Some(wgpu::Backends::DX12),
+         BackendChoice::Gl => Some(wgpu::Backends::GL),
+      }
+   }
+
+   pub fn from_backend(backend: wgpu::Backend) -> Self {
+      match backend {
+         wgpu::Backend::Vulkan => BackendChoice::Vulkan,
+         wgpu::Backend::Metal => BackendChoice::Metal,
+         wgpu::Backend::Dx12 => BackendChoice::Dx12,
+         wgpu::Backend::Gl => BackendChoice::Gl,
+         _ => BackendChoice::Auto,
+      }
+   }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, EnumIter, Debug, PartialEq)]
+pub enum PowerChoice {
+   /// discrete gpu first
+   HighPerformance,
+   /// integrated gpu first
+   LowPower,
+}
+
+impl PowerChoice {
+   pub fn preference(&self) -> wgpu::PowerPreference {
+      match self {
+         PowerChoice::HighPerformance => wgpu::PowerPreference::HighPerformance,
+         PowerChoice::LowPower => wgpu::PowerPreference::LowPower,
+      }
+   }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, EnumIter, Debug, PartialEq)]
+pub enum PresentChoice {
+   /// vsync, ``Fifo`` when nothing better is there
+   AutoVsync,
+   /// ``Immediate`` or ``Mailbox`` where supported, vsync otherwise
+   AutoNoVsync,
+   /// vsync, supported everywhere
+   Fifo,
+   /// no tearing without waiting, not everywhere
+   Mailbox,
+   /// tears, not everywhere
+   Immediate,
+}
+
+impl PresentChoice {
+   pub fn mode(&self) -> wgpu::PresentMode {
+      match self {
+         PresentChoice::AutoVsync => wgpu::PresentMode::AutoVsync,
+         PresentChoice::AutoNoVsync => wgpu::PresentMode::AutoNoVsync,
+         PresentChoice::Fifo => wgpu::PresentMode::Fifo,
+         PresentChoice::Mailbox => wgpu::PresentMode::Mailbox,
+         PresentChoice::Immediate => wgpu::PresentMode::Immediate,
+      }
+   }
+
+   pub fn is_vsync(&self) -> bool {
+      matches!(self, PresentChoice::AutoVsync | PresentChoice::Fifo)
+   }
+}
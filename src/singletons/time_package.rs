@@ -1,37 +1,106 @@
 use instant::Instant;
-use crate::init_static;
 
-// time singleton
-init_static!(TIME: TimePackage => { TimePackage::default() });
+use crate::global_utility::structs::RingBuffer;
 
 
-/// max for framerate averaging, turn down for performance
-const PAST_FPS_LIMIT: usize = 100;
+/// frames the percentiles and histogram are taken over
+pub const FRAME_TIME_HISTORY: usize = 1000;
 
 pub struct TimePackage {
    pub fps: f64,
-   pub fps_graphing: Vec<[f64; 2]>, // for graphing
+   pub fps_graphing: RingBuffer<[f64; 2]>, // for graphing
    pub delta_time: f64,
    frame_counter: u32,
 
    pub start_time: Instant,
    last_frame: Instant,
    last_data_dump: Instant,
-   past_delta_times: Vec<f64>,
+   /// summed up between fps updates
+   interval_time: f64,
+   interval_frames: u32,
+
+   /// milliseconds, newest overwrites oldest
+   pub frame_times: RingBuffer<f64>,
+   pub frame_stats: FrameStats,
+   /// a frame this many times slower than the median counts as a stutter
+   pub stutter_factor: f64,
+   /// since start or the last reset
+   pub total_stutters: u32,
+   pub last_stutter: Option<Instant>,
 
    pub fps_update_interval: f64,
    pub fps_amount: usize,
+
+   /// the next frame comes after the app went idle, its time is the wait and not the frame
+   after_idle: bool,
+}
+
+/// over the last ``FRAME_TIME_HISTORY`` frames, times in milliseconds
+#[derive(Default, Copy, Clone, serde::Serialize)]
+pub struct FrameStats {
+   pub p50: f64,
+   pub p95: f64,
+   pub p99: f64,
+   /// average fps of the slowest 1% of frames
+   pub one_percent_low: f64,
+   pub min: f64,
+   pub max: f64,
+   pub stutters: u32,
 }
+
+impl FrameStats {
+   /// ``times`` in milliseconds, none if empty
+   pub fn from_times(mut times: Vec<f64>, stutter_factor: f64) -> Option<Self> {
+      if times.is_empty() { return None; }
+      times.sort_by(f64::total_cmp);
+
+      let percentile = |p: f64| times[((times.len() - 1) as f64 * p).round() as usize];
+      let p50 = percentile(0.5);
+
+      // at least one frame, even with less than a hundred recorded
+      let slowest = &times[(times.len() as f64 * 0.99) as usize..];
+      let slowest_ms = slowest.iter().sum::<f64>() / slowest.len() as f64;
+
+      Some(Self {
+         p50,
+         p95: percentile(0.95),
+         p99: percentile(0.99),
+         one_percent_low: 1000.0 / slowest_ms,
+         min: times[0],
+         max: times[times.len() - 1],
+         stutters: times.iter().rev().take_while(|ms| **ms > p50 * stutter_factor).count() as u32,
+      })
+   }
+}
+
 impl TimePackage {
+   /// call when no repaint was requested, the wait until the next input is left out of the stats
+   pub fn idle(&mut self) {
+      self.after_idle = true;
+   }
+
    pub fn update(&mut self) {
       self.delta_time = self.last_frame.elapsed().as_secs_f64();
 
-      if self.past_delta_times.len() < PAST_FPS_LIMIT {
-         self.past_delta_times.push(self.delta_time);
+      if std::mem::take(&mut self.after_idle) {
+         self.last_frame = Instant::now();
+         self.frame_counter += 1;
+         return;
+      }
+
+      let frame_ms = self.delta_time * 1000.0;
+      self.frame_times.push(frame_ms);
+      self.interval_time += self.delta_time;
+      self.interval_frames += 1;
+
+      if self.frame_stats.p50 > 0.0 && frame_ms > self.frame_stats.p50 * self.stutter_factor {
+         self.total_stutters += 1;
+         self.last_stutter = Some(Instant::now());
       }
 
       if self.last_data_dump.elapsed().as_secs_f64() > 1.0 - self.fps_update_interval {
          self.calc_ave_fps();
+         self.calc_frame_stats();
          self.last_data_dump = Instant::now();
       }
 
@@ -40,36 +109,70 @@ impl TimePackage {
    }
 
    fn calc_ave_fps(&mut self) {
-      let mut total = 0.0;
-      for num in &self.past_delta_times {
-         total += num;
-      }
-      self.fps = 1.0 / (total / self.past_delta_times.len() as f64);
-      self.past_delta_times.clear();
+      self.fps = self.interval_frames as f64 / self.interval_time;
+      self.interval_time = 0.0;
+      self.interval_frames = 0;
+
+      self.fps_graphing.set_capacity(self.fps_amount);
       self.fps_graphing.push([self.frame_counter as f64, self.fps]);
+   }
+
+   fn calc_frame_stats(&mut self) {
+      let times = self.frame_times.iter().copied().collect::<Vec<_>>();
+      if let Some(stats) = FrameStats::from_times(times, self.stutter_factor) {
+         self.frame_stats = stats;
+      }
+   }
 
-      let diff = self.fps_graphing.len() as i32 - self.fps_amount as i32;
+   /// counts of frame times in ``bins`` even buckets from min to max, with where the first starts and the bucket width,
+   /// min and max come from the same times and not ``frame_stats`` which can be a second behind
+   pub fn histogram(&self, bins: usize) -> (Vec<u32>, f64, f64) {
+      if bins == 0 || self.frame_times.is_empty() { return (vec![0; bins], 0.0, 0.0); }
 
-      if diff > 0 {
-         self.fps_graphing.drain(0..(diff as usize));
+      let min = self.frame_times.iter().copied().fold(f64::INFINITY, f64::min);
+      let max = self.frame_times.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+      let width = ((max - min) / bins as f64).max(f64::EPSILON);
+
+      let mut counts = vec![0; bins];
+      for ms in self.frame_times.iter() {
+         let bin = ((ms - min) / width) as usize;
+         counts[bin.min(bins - 1)] += 1;
       }
+
+      (counts, min, width)
+   }
+
+   pub fn reset_frame_stats(&mut self) {
+      self.frame_times.clear();
+      self.frame_stats = FrameStats::default();
+      self.total_stutters = 0;
+      self.last_stutter = None;
    }
 }
 impl Default for TimePackage {
    fn default() -> Self {
       Self {
          fps: 0.0,
-         fps_graphing: vec![],
+         fps_graphing: RingBuffer::new(100),
          delta_time: 0.0,
          frame_counter: 0,
 
          start_time: Instant::now(),
          last_frame: Instant::now(),
          last_data_dump: Instant::now(),
-         past_delta_times: vec![],
+         interval_time: 0.0,
+         interval_frames: 0,
+
+         frame_times: RingBuffer::new(FRAME_TIME_HISTORY),
+         frame_stats: FrameStats::default(),
+         stutter_factor: 2.0,
+         total_stutters: 0,
+         last_stutter: None,
 
          fps_update_interval: 0.25,
          fps_amount: 100,
+
+         after_idle: false,
       }
    }
-}
\ No newline at end of file
+}
@@ -0,0 +1,262 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use egui::{DragValue, ProgressBar, Ui};
+use instant::Instant;
+
+use crate::path_tracer::render_utility::gpu_profiler::GpuFrame;
+
+/// ``tid`` of each track in the trace
+const CPU_TRACK: u32 = 1;
+const GPU_TRACK: u32 = 2;
+/// frames kept recording after the last one so the gpu readback can catch up
+const GPU_LAG_FRAMES: u32 = 8;
+
+/// records cpu and gpu scopes for a number of frames and writes them as a chrome
+/// ``trace_event`` json, opens in perfetto or ``chrome://tracing``
+pub struct TraceRecorder {
+   pub frames: u32,
+   pub output_path: String,
+
+   capture: Option<Capture>,
+   /// scopes ended since the last frame, shared with the ``TraceScope`` guards so they
+   /// don't hold on to the context, none while not recording
+   scopes: Rc<RefCell<Option<Vec<CpuScope>>>>,
+   /// timestamps in the trace are from here
+   origin: Instant,
+   status: String,
+}
+
+struct Capture {
+   start: Instant,
+   frames_left: u32,
+   total_frames: u32,
+   /// gpu frames submitted after this are left out, the cpu side stopped recording by then
+   cpu_end: Option<Instant>,
+   events: Vec<TraceEvent>,
+}
+
+struct CpuScope {
+   name: &'static str,
+   start: Instant,
+   end: Instant,
+}
+
+/// adds a cpu scope to the trace when dropped, from ``TraceRecorder::scope``
+pub struct TraceScope {
+   name: &'static str,
+   start: Instant,
+   scopes: Rc<RefCell<Option<Vec<CpuScope>>>>,
+}
+
+impl Drop for TraceScope {
+   fn drop(&mut self) {
+      if let Some(scopes) = self.scopes.borrow_mut().as_mut() {
+         scopes.push(CpuScope { name: self.name, start: self.start, end: Instant::now() });
+      }
+   }
+}
+
+#[derive(serde::Serialize)]
+struct TraceEvent {
+   name: String,
+   cat: &'static str,
+   ph: &'static str,
+   /// microseconds
+   ts: f64,
+   dur: f64,
+   pid: u32,
+   tid: u32,
+   #[serde(skip_serializing_if = "Option::is_none")]
+   args: Option<TraceArgs>,
+}
+
+#[derive(serde::Serialize)]
+struct TraceArgs {
+   name: String,
+}
+
+#[derive(serde::Serialize)]
+#[allow(non_snake_case)]
+struct TraceFile<'a> {
+   traceEvents: &'a [&'a TraceEvent],
+   displayTimeUnit: &'static str,
+}
+
+impl TraceRecorder {
+   pub fn is_recording(&self) -> bool {
+      self.capture.is_some()
+   }
+
+   pub fn start(&mut self) {
+      self.capture = Some(Capture {
+         start: Instant::now(),
+         frames_left: self.frames.max(1),
+         total_frames: self.frames.max(1),
+         cpu_end: None,
+         events: vec![],
+      });
+      *self.scopes.borrow_mut() = Some(vec![]);
+      self.status.clear();
+   }
+
+   fn stop(&mut self) {
+      self.capture = None;
+      *self.scopes.borrow_mut() = None;
+   }
+
+   /// a cpu scope from now until the guard is dropped, ``let _s = context.trace.scope("NAME");``
+   pub fn scope(&self, name: &'static str) -> TraceScope {
+      TraceScope { name, start: Instant::now(), scopes: self.scopes.clone() }
+   }
+
+   /// moves the scopes that ended since last frame into the capture,
+   /// scopes still open, like the whole frame, come in next frame
+   fn collect_scopes(&mut self) {
+      let origin = self.origin;
+      let Some(capture) = &mut self.capture else { return; };
+      let Some(scopes) = self.scopes.borrow_mut().as_mut().map(std::mem::take) else { return; };
+
+      for scope in scopes {
+         if capture.cpu_end.is_some_and(|end| scope.start > end) { continue; }
+
+         capture.events.push(TraceEvent {
+            name: scope.name.to_string(),
+            cat: "cpu",
+            ph: "X",
+            ts: micros(scope.start.duration_since(origin).as_secs_f64()),
+            dur: micros(scope.end.duration_since(scope.start).as_secs_f64()),
+            pid: 1,
+            tid: CPU_TRACK,
+            args: None,
+         });
+      }
+   }
+
+   /// gpu frames from ``GpuProfiler::take_captured``, placed at the cpu time they were resolved
+   pub fn gpu_frames(&mut self, frames: Vec<GpuFrame>) {
+      let origin = self.origin;
+      let Some(capture) = &mut self.capture else { return; };
+
+      for frame in frames {
+         if frame.submitted < capture.start || capture.cpu_end.is_some_and(|end| frame.submitted > end) { continue; }
+
+         let frame_start = frame.submitted.duration_since(origin).as_secs_f64();
+         for scope in frame.scopes {
+            capture.events.push(TraceEvent {
+               name: scope.key,
+               cat: "gpu",
+               ph: "X",
+               ts: micros(frame_start) + scope.offset_ms * 1000.0,
+               dur: scope.time_ms * 1000.0,
+               pid: 1,
+               tid: GPU_TRACK,
+               args: None,
+            });
+         }
+      }
+   }
+
+   /// counts the frame down, writes the file once done
+   pub fn end_frame(&mut self) {
+      self.collect_scopes();
+      let Some(capture) = &mut self.capture else { return; };
+
+      capture.frames_left = capture.frames_left.saturating_sub(1);
+      if capture.frames_left == 0 && capture.cpu_end.is_none() {
+         capture.cpu_end = Some(Instant::now());
+         capture.frames_left = GPU_LAG_FRAMES;
+         return;
+      }
+      if capture.frames_left > 0 { return; }
+
+      let capture = self.capture.take().unwrap();
+      self.stop();
+      self.status = match self.write(&capture.events) {
+         Ok(()) => format!("Saved {} events to {}", capture.events.len(), self.output_path),
+         Err(e) => format!("Couldn't save the trace: {e}"),
+      };
+   }
+
+   fn write(&self, events: &[TraceEvent]) -> std::io::Result<()> {
+      let names = [thread_name(CPU_TRACK, "CPU"), thread_name(GPU_TRACK, "GPU")];
+      let all = names.iter().chain(events).collect::<Vec<_>>();
+
+      let file = std::fs::File::create(&self.output_path)?;
+      let trace = TraceFile { traceEvents: &all, displayTimeUnit: "ms" };
+      serde_json::to_writer(std::io::BufWriter::new(file), &trace).map_err(std::io::Error::other)
+   }
+
+   pub fn ui(&mut self, ui: &mut Ui) {
+      ui.heading("Trace capture");
+
+      if cfg!(target_arch = "wasm32") {
+         ui.label("Only available on desktop");
+         return;
+      }
+
+      ui.add_enabled_ui(!self.is_recording(), |ui| {
+         ui.horizontal(|ui| {
+            ui.add(DragValue::new(&mut self.frames).range(1..=10000).prefix("Frames: "));
+            ui.label("Output");
+            ui.text_edit_singleline(&mut self.output_path);
+         });
+      });
+
+      match &self.capture {
+         None => {
+            if ui.button(format!("Record {} frames", self.frames)).clicked() {
+               self.start();
+            }
+         }
+         Some(capture) => {
+            let text = match capture.cpu_end {
+               None => format!("{} frames left", capture.frames_left),
+               Some(_) => "Waiting on the gpu".to_string(),
+            };
+            let done = if capture.cpu_end.is_some() { 1.0 } else { 1.0 - capture.frames_left as f32 / capture.total_frames as f32 };
+            ui.add(ProgressBar::new(done).text(text));
+
+            if ui.button("Cancel").clicked() {
+               self.stop();
+               self.status = "Cancelled".to_string();
+            }
+         }
+      }
+
+      if !self.status.is_empty() {
+         ui.label(&self.status);
+      }
+   }
+}
+
+impl Default for TraceRecorder {
+   fn default() -> Self {
+      Self {
+         frames: 120,
+         output_path: "trace.json".to_string(),
+         capture: None,
+         scopes: Rc::new(RefCell::new(None)),
+         origin: Instant::now(),
+         status: String::new(),
+      }
+   }
+}
+
+fn micros(seconds: f64) -> f64 {
+   seconds * 1_000_000.0
+}
+
+/// metadata event naming a track in the viewer
+fn thread_name(tid: u32, name: &str) -> TraceEvent {
+   TraceEvent {
+      name: "thread_name".to_string(),
+      cat: "__metadata",
+      ph: "M",
+      ts: 0.0,
+      dur: 0.0,
+      pid: 1,
+      tid,
+      args: Some(TraceArgs { name: name.to_string() }),
+   }
+}
@@ -0,0 +1,77 @@
+use strum::EnumIter;
+
+/// everything that can be run from the menus, the command palette or a key chord,
+/// menus list them in this order
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, EnumIter, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+   SaveScene,
+   /// the whole window with the ui, not just the render
+   #[serde(alias = "Screenshot")]
+   WindowScreenshot,
+   RecordTrace,
+   Restart,
+
+   Undo,
+   Redo,
+
+   NodeEditorPage,
+   StatsPage,
+   RenderQueuePage,
+   SettingsPage,
+   ThemeEditorPage,
+   ToggleProfiler,
+   CommandPalette,
+
+   ResetCamera,
+   RecompileShader,
+   ToggleDenoiser,
+   ToggleGpuProfiling,
+}
+
+/// top menu an action shows up in
+#[derive(Copy, Clone, EnumIter, Debug, PartialEq)]
+pub enum Menu {
+   File,
+   Edit,
+   View,
+   Render,
+}
+
+impl Action {
+   pub fn name(&self) -> &'static str {
+      match self {
+         Action::SaveScene => "Save scene",
+         Action::WindowScreenshot => "Window screenshot",
+         Action::RecordTrace => "Record trace",
+         Action::Restart => "Restart",
+         Action::Undo => "Undo",
+         Action::Redo => "Redo",
+         Action::NodeEditorPage => "Node editor page",
+         Action::StatsPage => "Stats page",
+         Action::RenderQueuePage => "Render queue page",
+         Action::SettingsPage => "Settings page",
+         Action::ThemeEditorPage => "Theme editor page",
+         Action::ToggleProfiler => "Toggle profiler window",
+         Action::CommandPalette => "Command palette",
+         Action::ResetCamera => "Reset camera",
+         Action::RecompileShader => "Recompile shader",
+         Action::ToggleDenoiser => "Toggle denoiser",
+         Action::ToggleGpuProfiling => "Toggle gpu profiling",
+      }
+   }
+
+   pub fn menu(&self) -> Menu {
+      match self {
+         Action::SaveScene | Action::WindowScreenshot | Action::RecordTrace | Action::Restart => Menu::File,
+         Action::Undo | Action::Redo => Menu::Edit,
+         Action::NodeEditorPage | Action::StatsPage | Action::RenderQueuePage | Action::SettingsPage
+         | Action::ThemeEditorPage | Action::ToggleProfiler | Action::CommandPalette => Menu::View,
+         Action::ResetCamera | Action::RecompileShader | Action::ToggleDenoiser | Action::ToggleGpuProfiling => Menu::Render,
+      }
+   }
+
+   /// "Menu: Name", what the command palette searches
+   pub fn label(&self) -> String {
+      format!("{:?}: {}", self.menu(), self.name())
+   }
+}
@@ -0,0 +1,114 @@
+use egui::{Align, Align2, Context, Key, Layout, Modifiers, ScrollArea, TextEdit, Window};
+
+use crate::user_interface::actions::Action;
+
+/// an action as the palette lists it
+pub struct PaletteEntry {
+   pub action: Action,
+   pub enabled: bool,
+   /// already formatted for the platform
+   pub shortcut: Option<String>,
+}
+
+/// fuzzy search over every action, opened with ctrl+p
+#[derive(Default)]
+pub struct CommandPalette {
+   open: bool,
+   query: String,
+   /// index into the filtered list
+   selected: usize,
+}
+
+impl CommandPalette {
+   pub fn toggle(&mut self) {
+      self.open = !self.open;
+      self.query.clear();
+      self.selected = 0;
+   }
+
+   /// returns the action picked this frame
+   pub fn ui(&mut self, ctx: &Context, entries: &[PaletteEntry]) -> Option<Action> {
+      if !self.open { return None; }
+
+      let mut matches = entries.iter()
+          .filter_map(|entry| Some((entry, fuzzy_score(&self.query, &entry.action.label())?)))
+          .collect::<Vec<_>>();
+      // stable so equal scores keep the menu order
+      matches.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+      self.selected = self.selected.min(matches.len().saturating_sub(1));
+
+      // taken before the text field sees them, it would move the cursor or drop focus
+      let (up, down, enter, escape) = ctx.input_mut(|i| (
+         i.consume_key(Modifiers::NONE, Key::ArrowUp),
+         i.consume_key(Modifiers::NONE, Key::ArrowDown),
+         i.consume_key(Modifiers::NONE, Key::Enter),
+         i.consume_key(Modifiers::NONE, Key::Escape),
+      ));
+      if up { self.selected = self.selected.saturating_sub(1); }
+      if down && self.selected + 1 < matches.len() { self.selected += 1; }
+
+      let mut picked = None;
+      if enter {
+         picked = matches.get(self.selected).filter(|(entry, _)| entry.enabled).map(|(entry, _)| entry.action);
+      }
+
+      Window::new("Command palette")
+          .title_bar(false)
+          .resizable(false)
+          .anchor(Align2::CENTER_TOP, [0.0, 80.0])
+          .fixed_size([400.0, 0.0])
+          .show(ctx, |ui| {
+             let edit = ui.add(TextEdit::singleline(&mut self.query).hint_text("Type a command").desired_width(f32::INFINITY));
+             edit.request_focus();
+             if edit.changed() { self.selected = 0; }
+
+             ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                for (i, (entry, _)) in matches.iter().enumerate() {
+                   ui.add_enabled_ui(entry.enabled, |ui| {
+                      ui.horizontal(|ui| {
+                         let row = ui.selectable_label(i == self.selected, entry.action.label());
+                         if i == self.selected && (up || down) { row.scroll_to_me(None); }
+                         if row.clicked() { picked = Some(entry.action); }
+
+                         if let Some(shortcut) = &entry.shortcut {
+                            ui.with_layout(Layout::right_to_left(Align::Center), |ui| ui.weak(shortcut));
+                         }
+                      });
+                   });
+                }
+
+                if matches.is_empty() { ui.weak("Nothing matches"); }
+             });
+          });
+
+      if picked.is_some() || escape {
+         self.toggle();
+      }
+      picked
+   }
+}
+
+/// none if ``query`` isn't in ``text`` in order, higher is a better match,
+/// runs of matched characters and matches at the start of words count more
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+   if query.trim().is_empty() { return Some(0); }
+
+   let text = text.to_lowercase().chars().collect::<Vec<_>>();
+   let mut score = 0;
+   let mut position = 0;
+   let mut last_match: Option<usize> = None;
+
+   for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+      let found = position + text[position..].iter().position(|t| *t == c)?;
+
+      score += 1;
+      if last_match.is_some_and(|last| last + 1 == found) { score += 5; }
+      if found == 0 || !text[found - 1].is_alphanumeric() { score += 3; }
+
+      last_match = Some(found);
+      position = found + 1;
+   }
+
+   // shorter labels win ties
+   Some(score * 100 - text.len() as i32)
+}
@@ -0,0 +1,330 @@
+use std::f32::consts::TAU;
+
+use egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Response, Stroke, Ui, Vec2};
+
+use crate::global_utility::functions::{add3, inv_rot3, mat3_axis_angle, mat3_from_euler, mat3_mul, mat3_mul_vec, mat3_to_euler, rot3, scale3};
+use crate::path_tracer::cpu_raymarcher::project;
+use crate::singletons::scene::{ParthtracerSettings, Transform};
+use crate::singletons::settings::{GizmoMode, GizmoSettings, GizmoSpace};
+
+/// length of the gizmo handles on screen
+const HANDLE_PIXELS: f32 = 80.0;
+/// how close the pointer has to be to grab a handle
+const GRAB_PIXELS: f32 = 8.0;
+const RING_SEGMENTS: usize = 48;
+
+const AXIS_COLORS: [Color32; 3] = [
+   Color32::from_rgb(230, 70, 70),
+   Color32::from_rgb(90, 200, 90),
+   Color32::from_rgb(80, 130, 240),
+];
+const ACTIVE_COLOR: Color32 = Color32::from_rgb(250, 210, 60);
+
+/// translate/rotate/scale handles painted over the viewport,
+/// scale is uniform (sdf's can't be stretched) so every scale handle scales the whole object
+pub struct Gizmo {
+   drag: Option<GizmoDrag>,
+}
+
+struct GizmoDrag {
+   axis: usize,
+   mode: GizmoMode,
+   start_pointer: Pos2,
+   start_transform: Transform,
+}
+
+/// the camera the viewport image was rendered with
+struct GizmoView<'a> {
+   rect: Rect,
+   camera: &'a ParthtracerSettings,
+   aspect: f32,
+}
+
+impl GizmoView<'_> {
+   fn to_screen(&self, p: [f32; 3]) -> Option<Pos2> {
+      project(self.camera, p, self.aspect)
+          .map(|(frac, _)| self.rect.min + Vec2::new(frac[0], frac[1]) * self.rect.size())
+   }
+
+   /// world size of ``HANDLE_PIXELS`` at ``p``, measured by projecting a small step
+   /// sideways from the camera so it works for every projection
+   fn handle_length(&self, p: [f32; 3]) -> Option<f32> {
+      const STEP: f32 = 0.01;
+      let side = rot3([1.0, 0.0, 0.0], self.camera.camera_dir);
+
+      let a = self.to_screen(p)?;
+      let b = self.to_screen(add3(p, scale3(side, STEP)))?;
+
+      let pixels_per_unit = a.distance(b) / STEP;
+      if pixels_per_unit > 1e-4 { Some(HANDLE_PIXELS / pixels_per_unit) } else { None }
+   }
+}
+
+impl Gizmo {
+   pub fn new() -> Self {
+      Self {
+         drag: None,
+      }
+   }
+
+   pub fn cancel(&mut self) {
+      self.drag = None;
+   }
+
+   /// paints the gizmo for ``transform`` on top of the viewport image and applies any dragging,
+   /// returns true while the gizmo is using the pointer so clicks don't fall through to picking
+   pub fn ui(
+      &mut self,
+      ui: &Ui,
+      response: &Response,
+      camera: &ParthtracerSettings,
+      aspect: f32,
+      gs: &GizmoSettings,
+      transform: &mut Transform,
+   ) -> bool {
+      let view = GizmoView { rect: response.rect, camera, aspect };
+
+      // axes and size are frozen while dragging so the handles don't move under the pointer
+      let base = self.drag.as_ref().map_or(*transform, |d| d.start_transform);
+      let (center, length) = match (view.to_screen(base.position), view.handle_length(base.position)) {
+         (Some(c), Some(l)) => (c, l),
+         _ => {
+            self.drag = None;
+            return false;
+         }
+      };
+      let axes = gizmo_axes(&base, gs.space);
+
+      let mode = self.drag.as_ref().map_or(gs.mode, |d| d.mode);
+      let handles: Vec<Vec<Pos2>> = (0..3)
+          .map(|axis| handle_points(&view, &base, &axes, axis, length, mode))
+          .collect();
+
+      // hovering
+      let hovered = if self.drag.is_none() {
+         response.hover_pos().and_then(|pos| closest_handle(&handles, pos))
+      } else {
+         None
+      };
+
+      // dragging
+      if response.drag_started() {
+         let origin = ui.input(|i| i.pointer.press_origin());
+         if let Some(axis) = origin.and_then(|pos| closest_handle(&handles, pos)) {
+            self.drag = Some(GizmoDrag {
+               axis,
+               mode,
+               start_pointer: origin.unwrap_or(center),
+               start_transform: *transform,
+            });
+         }
+      }
+
+      if let Some(drag) = &self.drag {
+         if let Some(pointer) = response.interact_pointer_pos() {
+            let snap = gs.snapping != ui.input(|i| i.modifiers.command);
+            *transform = drag.apply(&view, &axes, center, length, pointer, snap, gs);
+         }
+
+         if response.drag_stopped() || !response.dragged() {
+            self.drag = None;
+         }
+      }
+
+      // painting
+      let active = self.drag.as_ref().map(|d| d.axis).or(hovered);
+      paint_handles(&ui.painter_at(response.rect), &handles, center, mode, active);
+
+      active.is_some()
+   }
+}
+
+impl GizmoDrag {
+   #[allow(clippy::too_many_arguments)]
+   fn apply(&self, view: &GizmoView, axes: &[[f32; 3]; 3], center: Pos2, length: f32, pointer: Pos2, snap: bool, gs: &GizmoSettings) -> Transform {
+      let start = self.start_transform;
+      let axis = axes[self.axis];
+      let mut out = start;
+
+      match self.mode {
+         GizmoMode::Translate | GizmoMode::Scale => {
+            // how far the pointer moved along the projected axis, in world units
+            let tip = view.to_screen(add3(start.position, scale3(axis, length))).unwrap_or(center);
+            let screen_axis = tip - center;
+            let amount = if screen_axis.length_sq() > 1.0 {
+               (pointer - self.start_pointer).dot(screen_axis) / screen_axis.length_sq() * length
+            } else {
+               0.0
+            };
+
+            if self.mode == GizmoMode::Translate {
+               let amount = if snap { snap_to(amount, gs.translate_snap) } else { amount };
+               out.position = add3(start.position, scale3(axis, amount));
+            } else {
+               let scale = start.scale * (1.0 + amount / length);
+               let scale = if snap { snap_to(scale, gs.scale_snap) } else { scale };
+               out.scale = scale.max(0.001);
+            }
+         }
+
+         GizmoMode::Rotate => {
+            let start_angle = (self.start_pointer - center).angle();
+            let angle = wrap_angle((pointer - center).angle() - start_angle) * rotation_sign(view, &start, axes, self.axis, length);
+
+            let angle = if snap { snap_to(angle.to_degrees(), gs.rotate_snap).to_radians() } else { angle };
+
+            // the shader maps world -> local with this matrix so rotating the object
+            // by ``angle`` means rotating the mapping by ``-angle``
+            let m = mat3_mul(mat3_from_euler(start.rotation), mat3_axis_angle(axis, -angle));
+            out.rotation = mat3_to_euler(m);
+         }
+      }
+
+      out
+   }
+}
+
+
+/// small overlay for picking the gizmo mode and space, returns true if changed
+pub fn toolbar(ui: &mut Ui, rect: Rect, gs: &mut GizmoSettings) -> bool {
+   let mut changed = false;
+   let size = Vec2::new(20.0, 20.0);
+   let mut pos = rect.min + Vec2::new(30.0, 0.0);
+
+   for (mode, label) in [(GizmoMode::Translate, "T"), (GizmoMode::Rotate, "R"), (GizmoMode::Scale, "S")] {
+      let button_rect = Rect::from_min_size(pos, size);
+      if ui.put(button_rect, egui::SelectableLabel::new(gs.mode == mode, label)).on_hover_text(format!("{mode:?}")).clicked() {
+         gs.mode = mode;
+         changed = true;
+      }
+      pos.x += size.x + 2.0;
+   }
+
+   let space_label = match gs.space {
+      GizmoSpace::World => "World",
+      GizmoSpace::Local => "Local",
+   };
+   if ui.put(Rect::from_min_size(pos, Vec2::new(45.0, size.y)), egui::Button::new(space_label)).clicked() {
+      gs.space = match gs.space {
+         GizmoSpace::World => GizmoSpace::Local,
+         GizmoSpace::Local => GizmoSpace::World,
+      };
+      changed = true;
+   }
+
+   changed
+}
+
+
+/////////////
+// Helpers //
+/////////////
+fn gizmo_axes(transform: &Transform, space: GizmoSpace) -> [[f32; 3]; 3] {
+   let world = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+   match space {
+      GizmoSpace::World => world,
+      // the objects axes in world space
+      GizmoSpace::Local => world.map(|a| inv_rot3(a, transform.rotation)),
+   }
+}
+
+fn handle_points(view: &GizmoView, transform: &Transform, axes: &[[f32; 3]; 3], axis: usize, length: f32, mode: GizmoMode) -> Vec<Pos2> {
+   let center = transform.position;
+
+   match mode {
+      GizmoMode::Translate | GizmoMode::Scale => {
+         let tip = add3(center, scale3(axes[axis], length));
+         [center, tip].iter().filter_map(|p| view.to_screen(*p)).collect()
+      }
+
+      GizmoMode::Rotate => {
+         let u = axes[(axis + 1) % 3];
+         let v = axes[(axis + 2) % 3];
+         (0..=RING_SEGMENTS)
+             .filter_map(|i| {
+                let t = i as f32 / RING_SEGMENTS as f32 * TAU;
+                let offset = add3(scale3(u, t.cos() * length), scale3(v, t.sin() * length));
+                view.to_screen(add3(center, offset))
+             })
+             .collect()
+      }
+   }
+}
+
+fn closest_handle(handles: &[Vec<Pos2>], pos: Pos2) -> Option<usize> {
+   handles.iter()
+       .enumerate()
+       .map(|(i, points)| (i, polyline_distance(points, pos)))
+       .filter(|(_, d)| *d < GRAB_PIXELS)
+       .min_by(|a, b| a.1.total_cmp(&b.1))
+       .map(|(i, _)| i)
+}
+
+fn polyline_distance(points: &[Pos2], pos: Pos2) -> f32 {
+   points.windows(2)
+       .map(|w| segment_distance(w[0], w[1], pos))
+       .fold(f32::INFINITY, f32::min)
+}
+
+fn segment_distance(a: Pos2, b: Pos2, p: Pos2) -> f32 {
+   let ab = b - a;
+   let t = if ab.length_sq() > 0.0 { ((p - a).dot(ab) / ab.length_sq()).clamp(0.0, 1.0) } else { 0.0 };
+   (a + ab * t).distance(p)
+}
+
+/// which way a positive rotation around ``axis`` turns on screen, the ring
+/// flips direction depending on whether the axis faces the camera
+fn rotation_sign(view: &GizmoView, transform: &Transform, axes: &[[f32; 3]; 3], axis: usize, length: f32) -> f32 {
+   let u = scale3(axes[(axis + 1) % 3], length);
+   let turned = mat3_mul_vec(mat3_axis_angle(axes[axis], 0.1), u);
+
+   let screen = (
+      view.to_screen(transform.position),
+      view.to_screen(add3(transform.position, u)),
+      view.to_screen(add3(transform.position, turned)),
+   );
+
+   match screen {
+      (Some(c), Some(a), Some(b)) => {
+         let d = wrap_angle((b - c).angle() - (a - c).angle());
+         if d < 0.0 { -1.0 } else { 1.0 }
+      }
+      _ => 1.0,
+   }
+}
+
+fn paint_handles(painter: &Painter, handles: &[Vec<Pos2>], center: Pos2, mode: GizmoMode, active: Option<usize>) {
+   for (axis, points) in handles.iter().enumerate() {
+      let color = if active == Some(axis) { ACTIVE_COLOR } else { AXIS_COLORS[axis] };
+      let stroke = Stroke::new(2.5, color);
+
+      match mode {
+         GizmoMode::Translate | GizmoMode::Scale => {
+            if let [start, tip] = points[..] {
+               painter.line_segment([start, tip], stroke);
+
+               if mode == GizmoMode::Translate {
+                  painter.circle_filled(tip, 5.0, color);
+               } else {
+                  painter.rect_filled(Rect::from_center_size(tip, Vec2::splat(9.0)), 0.0, color);
+               }
+               painter.text(tip + (tip - start).normalized() * 10.0, Align2::CENTER_CENTER, ["X", "Y", "Z"][axis], FontId::monospace(11.0), color);
+            }
+         }
+
+         GizmoMode::Rotate => {
+            painter.add(egui::Shape::line(points.clone(), stroke));
+         }
+      }
+   }
+
+   painter.circle_filled(center, 3.0, Color32::WHITE);
+}
+
+fn snap_to(v: f32, step: f32) -> f32 {
+   if step > 0.0 { (v / step).round() * step } else { v }
+}
+
+fn wrap_angle(a: f32) -> f32 {
+   (a + std::f32::consts::PI).rem_euclid(TAU) - std::f32::consts::PI
+}
@@ -0,0 +1,173 @@
+use egui::{Context, Event, Grid, Key, KeyboardShortcut, Modifiers, Ui};
+use strum::IntoEnumIterator;
+
+use crate::user_interface::actions::Action;
+
+/// chord an action starts with, none leaves it unbound
+fn default_shortcut(action: Action) -> Option<KeyboardShortcut> {
+   let (modifiers, key) = match action {
+      Action::SaveScene => (Modifiers::COMMAND, Key::S),
+      Action::WindowScreenshot => (Modifiers::NONE, Key::F12),
+      Action::Undo => (Modifiers::COMMAND, Key::Z),
+      Action::Redo => (Modifiers::COMMAND | Modifiers::SHIFT, Key::Z),
+      Action::NodeEditorPage => (Modifiers::COMMAND, Key::Num1),
+      Action::StatsPage => (Modifiers::COMMAND, Key::Num2),
+      Action::RenderQueuePage => (Modifiers::COMMAND, Key::Num3),
+      Action::SettingsPage => (Modifiers::COMMAND, Key::Num4),
+      Action::ThemeEditorPage => (Modifiers::COMMAND, Key::Num5),
+      Action::ToggleProfiler => (Modifiers::NONE, Key::F3),
+      Action::CommandPalette => (Modifiers::COMMAND, Key::P),
+      Action::ResetCamera => (Modifiers::NONE, Key::Home),
+      Action::RecompileShader => (Modifiers::NONE, Key::F5),
+      Action::ToggleDenoiser => (Modifiers::NONE, Key::F6),
+      Action::RecordTrace | Action::Restart | Action::ToggleGpuProfiling => return None,
+   };
+   Some(KeyboardShortcut::new(modifiers, key))
+}
+
+/// key chords for each ``Action``, saved in ``Settings``
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(default)]
+pub struct Keymap {
+   bindings: Vec<Binding>,
+
+   /// action waiting for a key press to be bound to
+   #[serde(skip)]
+   capturing: Option<Action>,
+   /// whether ``ui`` ran since the last ``pressed``, leaving the page drops the capture
+   #[serde(skip)]
+   shown: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone)]
+struct Binding {
+   action: Action,
+   shortcut: Option<KeyboardShortcut>,
+}
+
+impl Default for Keymap {
+   fn default() -> Self {
+      Self {
+         bindings: Action::iter().map(|action| Binding { action, shortcut: default_shortcut(action) }).collect(),
+         capturing: None,
+         shown: false,
+      }
+   }
+}
+
+impl Keymap {
+   pub fn shortcut(&self, action: Action) -> Option<KeyboardShortcut> {
+      match self.bindings.iter().find(|b| b.action == action) {
+         Some(binding) => binding.shortcut,
+         // saved before the action existed
+         None => default_shortcut(action),
+      }
+   }
+
+   /// actions whose chord was pressed this frame, nothing while a text field has focus or a key is being bound
+   pub fn pressed(&mut self, ctx: &Context) -> Vec<Action> {
+      if !std::mem::take(&mut self.shown) {
+         self.capturing = None;
+      }
+      if self.capturing.is_some() || ctx.wants_keyboard_input() { return vec![]; }
+
+      // egui ignores extra shift and alt when matching, so the ones using more modifiers go first
+      let mut bindings = Action::iter()
+          .filter_map(|action| Some((action, self.shortcut(action)?)))
+          .collect::<Vec<_>>();
+      bindings.sort_by_key(|(_, shortcut)| std::cmp::Reverse(modifier_count(shortcut.modifiers)));
+
+      ctx.input_mut(|i| {
+         bindings.into_iter()
+             .filter(|(_, shortcut)| i.consume_shortcut(shortcut))
+             .map(|(action, _)| action)
+             .collect()
+      })
+   }
+
+   /// other actions on the same chord as ``action``
+   fn conflicts(&self, action: Action) -> Vec<Action> {
+      let Some(shortcut) = self.shortcut(action) else { return vec![]; };
+
+      Action::iter()
+          .filter(|other| *other != action && self.shortcut(*other) == Some(shortcut))
+          .collect()
+   }
+
+   fn set(&mut self, action: Action, shortcut: Option<KeyboardShortcut>) {
+      match self.bindings.iter_mut().find(|b| b.action == action) {
+         Some(binding) => binding.shortcut = shortcut,
+         None => self.bindings.push(Binding { action, shortcut }),
+      }
+   }
+}
+
+
+////////
+// Ui //
+////////
+impl Keymap {
+   pub fn ui(&mut self, ui: &mut Ui) {
+      self.shown = true;
+      self.capture(ui);
+
+      Grid::new("keymap").num_columns(3).striped(true).show(ui, |ui| {
+         for action in Action::iter() {
+            ui.label(action.name());
+
+            let text = match (self.capturing == Some(action), self.shortcut(action)) {
+               (true, _) => "Press a key...".to_string(),
+               (false, Some(shortcut)) => ui.ctx().format_shortcut(&shortcut),
+               (false, None) => "Unbound".to_string(),
+            };
+            if ui.button(text).on_hover_text("Click and press the new chord, escape cancels").clicked() {
+               self.capturing = Some(action);
+            }
+
+            ui.horizontal(|ui| {
+               if ui.small_button("✖").on_hover_text("Unbind").clicked() {
+                  self.set(action, None);
+               }
+
+               let conflicts = self.conflicts(action);
+               if !conflicts.is_empty() {
+                  let names = conflicts.iter().map(|a| a.name()).collect::<Vec<_>>().join(", ");
+                  ui.colored_label(ui.visuals().warn_fg_color, format!("Also bound to {names}"));
+               }
+            });
+            ui.end_row();
+         }
+      });
+
+      if ui.button("Reset to defaults").clicked() {
+         *self = Self::default();
+      }
+   }
+
+   /// binds the next key pressed to the action being captured
+   fn capture(&mut self, ui: &mut Ui) {
+      let Some(action) = self.capturing else { return; };
+
+      let pressed = ui.input(|i| i.events.iter().find_map(|event| match event {
+         Event::Key { key, pressed: true, modifiers, .. } => Some((*key, *modifiers)),
+         _ => None,
+      }));
+      let Some((key, modifiers)) = pressed else { return; };
+
+      // ctrl or cmd comes along with command depending on the platform, only command is
+      // kept so chords compare equal to the defaults and work on every platform
+      let modifiers = if modifiers.command { Modifiers { ctrl: false, mac_cmd: false, ..modifiers } } else { modifiers };
+
+      if key != Key::Escape {
+         self.set(action, Some(KeyboardShortcut::new(modifiers, key)));
+      }
+      self.capturing = None;
+   }
+}
+
+fn modifier_count(modifiers: Modifiers) -> u32 {
+   [modifiers.alt, modifiers.shift, modifiers.ctrl || modifiers.command || modifiers.mac_cmd]
+       .iter()
+       .filter(|m| **m)
+       .count() as u32
+}
@@ -0,0 +1,347 @@
+use catppuccin_egui::MOCHA;
+use egui::{Button, CollapsingHeader, Color32, ComboBox, DragValue, Grid, Rounding, ScrollArea, Style, TextEdit, TextStyle, Ui, Vec2};
+use strum::IntoEnumIterator;
+
+use crate::singletons::settings::{Settings, Theme};
+
+/// a named catppuccin palette with a few style changes on top, saved in ``Settings``
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub struct CustomTheme {
+   pub name: String,
+   /// in the order of ``palette_fields``
+   pub colors: [Color32; 26],
+   pub style: StyleTweaks,
+}
+
+impl CustomTheme {
+   pub fn new(name: String, palette: catppuccin_egui::Theme) -> Self {
+      let mut theme = Self {
+         name,
+         colors: [Color32::BLACK; 26],
+         style: StyleTweaks::default(),
+      };
+      theme.set_palette(palette);
+      theme
+   }
+
+   pub fn set_palette(&mut self, mut palette: catppuccin_egui::Theme) {
+      self.colors = palette_fields(&mut palette).map(|(_, color)| *color);
+   }
+
+   pub fn palette(&self) -> catppuccin_egui::Theme {
+      let mut palette = MOCHA;
+      for ((_, color), saved) in palette_fields(&mut palette).into_iter().zip(self.colors) {
+         *color = saved;
+      }
+      palette
+   }
+}
+
+/// defaults are egui's own
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, PartialEq)]
+#[serde(default)]
+pub struct StyleTweaks {
+   /// multiplies every text style
+   pub font_scale: f32,
+   pub widget_rounding: f32,
+   pub window_rounding: f32,
+   pub item_spacing: [f32; 2],
+   pub button_padding: [f32; 2],
+}
+
+impl Default for StyleTweaks {
+   fn default() -> Self {
+      Self {
+         font_scale: 1.0,
+         widget_rounding: 2.0,
+         window_rounding: 6.0,
+         item_spacing: [8.0, 3.0],
+         button_padding: [4.0, 1.0],
+      }
+   }
+}
+
+impl StyleTweaks {
+   /// on top of whatever visuals the theme set, fonts start from egui's sizes so scaling doesn't stack
+   pub fn apply(&self, style: &mut Style) {
+      style.text_styles = Style::default().text_styles;
+      for font in style.text_styles.values_mut() {
+         font.size *= self.font_scale;
+      }
+
+      style.spacing.item_spacing = Vec2::from(self.item_spacing);
+      style.spacing.button_padding = Vec2::from(self.button_padding);
+
+      let widgets = &mut style.visuals.widgets;
+      for widget in [&mut widgets.noninteractive, &mut widgets.inactive, &mut widgets.hovered, &mut widgets.active, &mut widgets.open] {
+         widget.rounding = Rounding::same(self.widget_rounding);
+      }
+      style.visuals.window_rounding = Rounding::same(self.window_rounding);
+      style.visuals.menu_rounding = Rounding::same(self.window_rounding);
+   }
+
+   fn ui(&mut self, ui: &mut Ui) -> bool {
+      let mut changed = false;
+
+      Grid::new("style tweaks").num_columns(2).show(ui, |ui| {
+         ui.label("Font scale");
+         changed |= ui.add(DragValue::new(&mut self.font_scale).range(0.5..=3.0).speed(0.01)).changed();
+         ui.end_row();
+
+         ui.label("Rounding");
+         ui.horizontal(|ui| {
+            changed |= ui.add(DragValue::new(&mut self.widget_rounding).range(0.0..=20.0).speed(0.1).prefix("Widgets: ")).changed();
+            changed |= ui.add(DragValue::new(&mut self.window_rounding).range(0.0..=30.0).speed(0.1).prefix("Windows: ")).changed();
+         });
+         ui.end_row();
+
+         ui.label("Item spacing");
+         ui.horizontal(|ui| {
+            changed |= ui.add(DragValue::new(&mut self.item_spacing[0]).range(0.0..=30.0).speed(0.1).prefix("X: ")).changed();
+            changed |= ui.add(DragValue::new(&mut self.item_spacing[1]).range(0.0..=30.0).speed(0.1).prefix("Y: ")).changed();
+         });
+         ui.end_row();
+
+         ui.label("Button padding");
+         ui.horizontal(|ui| {
+            changed |= ui.add(DragValue::new(&mut self.button_padding[0]).range(0.0..=30.0).speed(0.1).prefix("X: ")).changed();
+            changed |= ui.add(DragValue::new(&mut self.button_padding[1]).range(0.0..=30.0).speed(0.1).prefix("Y: ")).changed();
+         });
+         ui.end_row();
+      });
+
+      if ui.button("Reset style").clicked() {
+         *self = Self::default();
+         changed = true;
+      }
+      changed
+   }
+}
+
+
+/////////////////
+// Editor page //
+/////////////////
+#[derive(Default)]
+pub struct ThemeEditor {
+   /// index into ``Settings::custom_themes``
+   editing: Option<usize>,
+   import_text: String,
+   status: String,
+}
+
+impl ThemeEditor {
+   pub fn ui(&mut self, ui: &mut Ui, settings: &mut Settings) {
+      ui.heading("Theme editor");
+
+      ui.group(|ui| {
+         self.list_ui(ui, settings);
+      });
+
+      if let Some(index) = self.editing.filter(|i| *i < settings.custom_themes.len()) {
+         ui.group(|ui| {
+            self.edit_ui(ui, settings, index);
+         });
+      }
+
+      ui.group(|ui| {
+         self.import_ui(ui, settings);
+      });
+
+      if !self.status.is_empty() {
+         ui.label(&self.status);
+      }
+   }
+
+   fn list_ui(&mut self, ui: &mut Ui, settings: &mut Settings) {
+      ui.horizontal(|ui| {
+         ui.label("Custom themes");
+
+         if ui.button("New from current").clicked() {
+            let name = format!("Theme {}", settings.custom_themes.len() + 1);
+            let theme = match settings.theme {
+               Theme::Custom(i) => settings.custom_themes.get(i).map(|t| CustomTheme { name: name.clone(), ..t.clone() }),
+               builtin => builtin.palette().map(|palette| CustomTheme::new(name.clone(), palette)),
+            };
+
+            settings.custom_themes.push(theme.unwrap_or_else(|| CustomTheme::new(name, MOCHA)));
+            self.editing = Some(settings.custom_themes.len() - 1);
+         }
+      });
+
+      let mut remove = None;
+      let mut use_theme = None;
+      for (i, theme) in settings.custom_themes.iter().enumerate() {
+         ui.horizontal(|ui| {
+            if ui.selectable_label(self.editing == Some(i), &theme.name).clicked() {
+               self.editing = Some(i);
+            }
+
+            if ui.add_enabled(settings.theme != Theme::Custom(i), Button::new("Use")).clicked() {
+               use_theme = Some(i);
+            }
+            if ui.button("🗑").clicked() { remove = Some(i); }
+         });
+      }
+
+      if let Some(i) = use_theme {
+         settings.theme = Theme::Custom(i);
+         settings.apply_theme(ui.ctx());
+      }
+
+      if let Some(i) = remove {
+         settings.custom_themes.remove(i);
+         self.editing = None;
+
+         // indices after the removed one move down
+         settings.theme = match settings.theme {
+            Theme::Custom(t) if t == i => Theme::Dark,
+            Theme::Custom(t) if t > i => Theme::Custom(t - 1),
+            theme => theme,
+         };
+         settings.apply_theme(ui.ctx());
+      }
+   }
+
+   fn edit_ui(&mut self, ui: &mut Ui, settings: &mut Settings, index: usize) {
+      let theme = &mut settings.custom_themes[index];
+      let mut changed = false;
+
+      ui.horizontal(|ui| {
+         ui.label("Name");
+         ui.add(TextEdit::singleline(&mut theme.name).desired_width(200.0));
+
+         if ui.button("Export").on_hover_text("Copies the theme as json, and saves it next to the app on desktop").clicked() {
+            self.status = export(ui, theme);
+         }
+      });
+
+      CollapsingHeader::new("Colors").default_open(true).show(ui, |ui| {
+         let mut palette = theme.palette();
+         if theme_color_picker(ui, &mut palette) {
+            theme.set_palette(palette);
+            changed = true;
+         }
+      });
+
+      CollapsingHeader::new("Style").default_open(true).show(ui, |ui| {
+         changed |= theme.style.ui(ui);
+      });
+
+      // live preview when editing the one in use
+      if changed && settings.theme == Theme::Custom(index) {
+         settings.apply_theme(ui.ctx());
+      }
+   }
+
+   fn import_ui(&mut self, ui: &mut Ui, settings: &mut Settings) {
+      ui.label("Import");
+      ScrollArea::vertical().max_height(100.0).show(ui, |ui| {
+         ui.add(TextEdit::multiline(&mut self.import_text)
+             .hint_text("Paste an exported theme")
+             .desired_width(f32::INFINITY)
+             .font(TextStyle::Monospace));
+      });
+
+      if ui.button("Import").clicked() {
+         match serde_json::from_str::<CustomTheme>(&self.import_text) {
+            Ok(theme) => {
+               self.status = format!("Imported {}", theme.name);
+               settings.custom_themes.push(theme);
+               self.editing = Some(settings.custom_themes.len() - 1);
+               self.import_text.clear();
+            }
+            Err(e) => self.status = format!("Not a theme: {e}"),
+         }
+      }
+   }
+}
+
+/// the built in themes followed by the custom ones, true if the selection changed
+pub fn theme_combination_box(ui: &mut Ui, settings: &mut Settings) -> bool {
+   let before = settings.theme;
+
+   ComboBox::from_label("Theme")
+       .selected_text(theme_name(settings, settings.theme))
+       .show_ui(ui, |ui| {
+          for theme in Theme::iter().filter(|t| !matches!(t, Theme::Custom(_))) {
+             ui.selectable_value(&mut settings.theme, theme, format!("{theme:?}"));
+          }
+
+          if !settings.custom_themes.is_empty() { ui.separator(); }
+          for (i, custom) in settings.custom_themes.iter().enumerate() {
+             ui.selectable_value(&mut settings.theme, Theme::Custom(i), &custom.name);
+          }
+       });
+
+   settings.theme != before
+}
+
+fn theme_name(settings: &Settings, theme: Theme) -> String {
+   match theme {
+      Theme::Custom(i) => settings.custom_themes.get(i).map_or("Missing".to_string(), |t| t.name.clone()),
+      builtin => format!("{builtin:?}"),
+   }
+}
+
+fn export(ui: &mut Ui, theme: &CustomTheme) -> String {
+   let json = serde_json::to_string_pretty(theme).unwrap();
+   ui.output_mut(|o| o.copied_text = json.clone());
+
+   if cfg!(target_arch = "wasm32") {
+      return "Copied to the clipboard".to_string();
+   }
+
+   let path = format!("{}.theme.json", theme.name.replace(|c: char| !c.is_alphanumeric(), "_"));
+   match std::fs::write(&path, json) {
+      Ok(()) => format!("Copied to the clipboard and saved {path}"),
+      Err(e) => format!("Copied to the clipboard, couldn't save {path}: {e}"),
+   }
+}
+
+/// every color of a catppuccin palette with its name
+fn palette_fields(theme: &mut catppuccin_egui::Theme) -> [(&'static str, &mut Color32); 26] {
+   [
+      ("Rosewater", &mut theme.rosewater),
+      ("Flamingo", &mut theme.flamingo),
+      ("Pink", &mut theme.pink),
+      ("Mauve", &mut theme.mauve),
+      ("Red", &mut theme.red),
+      ("Maroon", &mut theme.maroon),
+      ("Peach", &mut theme.peach),
+      ("Yellow", &mut theme.yellow),
+      ("Green", &mut theme.green),
+      ("Teal", &mut theme.teal),
+      ("Sky", &mut theme.sky),
+      ("Sapphire", &mut theme.sapphire),
+      ("Blue", &mut theme.blue),
+      ("Lavender", &mut theme.lavender),
+      ("Text", &mut theme.text),
+      ("Subtext 1", &mut theme.subtext1),
+      ("Subtext 0", &mut theme.subtext0),
+      ("Overlay 2", &mut theme.overlay2),
+      ("Overlay 1", &mut theme.overlay1),
+      ("Overlay 0", &mut theme.overlay0),
+      ("Surface 2", &mut theme.surface2),
+      ("Surface 1", &mut theme.surface1),
+      ("Surface 0", &mut theme.surface0),
+      ("Base", &mut theme.base),
+      ("Mantle", &mut theme.mantle),
+      ("Crust", &mut theme.crust),
+   ]
+}
+
+/// edits all 26 colors in two columns, returns true if any changed
+pub fn theme_color_picker(ui: &mut Ui, theme: &mut catppuccin_egui::Theme) -> bool {
+   let mut changed = false;
+
+   Grid::new("theme colors").num_columns(4).show(ui, |ui| {
+      for (i, (name, color)) in palette_fields(theme).into_iter().enumerate() {
+         ui.label(name);
+         changed |= ui.color_edit_button_srgba(color).changed();
+         if i % 2 == 1 { ui.end_row(); }
+      }
+   });
+
+   changed
+}
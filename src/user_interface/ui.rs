@@ -1,14 +1,19 @@
 use crate::app::PROF;
 use eframe::{CreationContext, Storage};
-use egui::{CentralPanel, CollapsingHeader, ComboBox, DragValue, FontId, RichText, ScrollArea, SidePanel, Slider, TopBottomPanel, Ui, Vec2b, Window};
-use egui_plot::{Corner, Legend, Line, Plot};
+use egui::{Button, CentralPanel, CollapsingHeader, Color32, ComboBox, Context, DragValue, Event, FontId, Grid, RichText, ScrollArea, SidePanel, Slider, TopBottomPanel, Ui, Vec2b, ViewportCommand, Window};
+use egui_plot::{Bar, BarChart, Corner, Legend, Line, Plot};
 
 use serde_json::{from_str, to_string};
+use strum::IntoEnumIterator;
 use triglyceride::open_profiler;
-use crate::{get, get_mut, get_mut_ref};
 use crate::app::MgsApp;
-use crate::singletons::settings::SETTINGS;
-use crate::singletons::time_package::TIME;
+use crate::path_tracer::render_utility::device_features;
+use crate::path_tracer::render_utility::gpu_profiler::TimestampMode;
+use crate::singletons::settings::Settings;
+use crate::singletons::time_package::TimePackage;
+use crate::user_interface::actions::{Action, Menu};
+use crate::user_interface::command_palette::PaletteEntry;
+use crate::user_interface::theme_editor::theme_combination_box;
 use crate::user_interface::ui_modules::{enum_combination_box, ToggleSwitch};
 
 #[derive(Copy, Clone)]
@@ -16,7 +21,9 @@ use crate::user_interface::ui_modules::{enum_combination_box, ToggleSwitch};
 enum MainContentPage {
    NodeEditor,
    Stats,
+   RenderQueue,
    Settings,
+   ThemeEditor,
 }
 
 
@@ -53,6 +60,114 @@ impl Default for UiState {
 }
 
 
+/// actions bound to keys
+impl MgsApp {
+   pub fn handle_shortcuts(&mut self, ctx: &Context) {
+      for action in self.context.settings.keymap.pressed(ctx) {
+         if self.action_enabled(action) { self.run_action(ctx, action); }
+      }
+      self.save_screenshots(ctx);
+   }
+
+   pub fn action_enabled(&self, action: Action) -> bool {
+      let desktop = cfg!(not(target_arch = "wasm32"));
+      // the benchmark has the scene and camera while it runs
+      let editable = !self.path_tracer.benchmark.is_running();
+
+      match action {
+         Action::Undo => editable && self.context.history.can_undo(),
+         Action::Redo => editable && self.context.history.can_redo(),
+         Action::SaveScene | Action::ResetCamera => editable,
+         Action::WindowScreenshot | Action::Restart => desktop,
+         Action::RecordTrace => desktop && !self.context.trace.is_recording(),
+         Action::ToggleGpuProfiling => desktop && self.path_tracer.gpu_profiler.mode != TimestampMode::Unsupported,
+         _ => true,
+      }
+   }
+
+   pub fn run_action(&mut self, ctx: &Context, action: Action) {
+      let settings = &mut self.context.settings;
+
+      match action {
+         Action::SaveScene => settings.saved_scenes.push(settings.current_scene.clone()),
+         Action::WindowScreenshot => ctx.send_viewport_cmd(ViewportCommand::Screenshot),
+         Action::RecordTrace => self.context.trace.start(),
+         Action::Restart => self.restart(),
+         Action::Undo => self.context.history.undo(&mut settings.current_scene),
+         Action::Redo => self.context.history.redo(&mut settings.current_scene),
+         Action::NodeEditorPage => self.ui_state.main_content_page = MainContentPage::NodeEditor,
+         Action::StatsPage => self.ui_state.main_content_page = MainContentPage::Stats,
+         Action::RenderQueuePage => self.ui_state.main_content_page = MainContentPage::RenderQueue,
+         Action::SettingsPage => self.ui_state.main_content_page = MainContentPage::Settings,
+         Action::ThemeEditorPage => self.ui_state.main_content_page = MainContentPage::ThemeEditor,
+         Action::ToggleProfiler => self.ui_state.floating_profiler_window = !self.ui_state.floating_profiler_window,
+         Action::CommandPalette => self.command_palette.toggle(),
+         Action::ResetCamera => self.path_tracer.reset_camera(&mut settings.current_scene),
+         Action::RecompileShader => self.path_tracer.recompile_shader(),
+         Action::ToggleDenoiser => settings.denoise_settings.enabled = !settings.denoise_settings.enabled,
+         Action::ToggleGpuProfiling => self.path_tracer.do_gpu_profiling = !self.path_tracer.do_gpu_profiling,
+      }
+   }
+
+   /// menu item with its shortcut, true when clicked
+   fn action_button(&self, ui: &mut Ui, action: Action) -> bool {
+      let mut button = Button::new(action.name());
+      if let Some(shortcut) = self.context.settings.keymap.shortcut(action) {
+         button = button.shortcut_text(ui.ctx().format_shortcut(&shortcut));
+      }
+
+      ui.add_enabled(self.action_enabled(action), button).clicked()
+   }
+
+   fn command_palette(&mut self, ctx: &Context) {
+      let entries = Action::iter().map(|action| PaletteEntry {
+         action,
+         enabled: self.action_enabled(action),
+         shortcut: self.context.settings.keymap.shortcut(action).map(|s| ctx.format_shortcut(&s)),
+      }).collect::<Vec<_>>();
+
+      if let Some(action) = self.command_palette.ui(ctx, &entries) {
+         self.run_action(ctx, action);
+      }
+   }
+
+   /// writes screenshots the viewport sent back this frame as binary ppm
+   fn save_screenshots(&self, ctx: &Context) {
+      let images = ctx.input(|i| i.raw.events.iter().filter_map(|event| match event {
+         Event::Screenshot { image, .. } => Some(image.clone()),
+         _ => None,
+      }).collect::<Vec<_>>());
+
+      for image in images {
+         let path = screenshot_path();
+         let [width, height] = image.size;
+
+         let mut data = format!("P6\n{width} {height}\n255\n").into_bytes();
+         data.extend(image.pixels.iter().flat_map(|c| [c.r(), c.g(), c.b()]));
+
+         match std::fs::write(&path, data) {
+            Ok(()) => log::info!("saved {path}"),
+            Err(e) => log::error!("couldn't save {path}: {e}"),
+         }
+      }
+   }
+}
+
+/// named by milliseconds since the unix epoch so they sort by time, with a counter
+/// after it if one was already taken that millisecond
+fn screenshot_path() -> String {
+   let millis = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_millis());
+
+   let mut path = format!("screenshot_{millis}.ppm");
+   let mut count = 1;
+   while std::path::Path::new(&path).exists() {
+      path = format!("screenshot_{millis}_{count}.ppm");
+      count += 1;
+   }
+   path
+}
+
+
 /// main user_interface areas
 impl MgsApp {
    /// handles sectioning
@@ -69,6 +184,7 @@ impl MgsApp {
          })
       }
 
+      self.command_palette(&ui.ctx().clone());
       self.top_menubar(ui);
 
       SidePanel::left("Left menubar")
@@ -111,13 +227,23 @@ impl MgsApp {
    fn top_menubar(&mut self, ui: &mut Ui) {
       ui.group(|ui| {
          egui::menu::bar(ui, |ui| {
-            ui.menu_button("File", |ui| {
-               if ui.button("restart").clicked() { self.restart() };
-            });
+            let mut clicked = None;
+
+            for menu in Menu::iter() {
+               ui.menu_button(format!("{menu:?}"), |ui| {
+                  for action in Action::iter().filter(|action| action.menu() == menu) {
+                     if self.action_button(ui, action) {
+                        clicked = Some(action);
+                        ui.close_menu();
+                     }
+                  }
+               });
+            }
 
-            ui.menu_button("Edit", |ui| {
-               if ui.button("test").clicked() {};
-               if ui.button("test2").clicked() {};
+            if let Some(action) = clicked { self.run_action(&ui.ctx().clone(), action); }
+
+            ui.menu_button("Benchmark", |ui| {
+               self.path_tracer.benchmark.ui(ui);
             });
          });
       });
@@ -139,15 +265,25 @@ impl MgsApp {
       }
       ui.add_space(SPACE);
 
+      if ui.button(large_emoji("🎞")).clicked() {
+         self.ui_state.main_content_page = MainContentPage::RenderQueue;
+      }
+      ui.add_space(SPACE);
+
       if ui.button(large_emoji("🔧")).clicked() {
          self.ui_state.main_content_page = MainContentPage::Settings;
       }
       ui.add_space(SPACE);
+
+      if ui.button(large_emoji("🎨")).clicked() {
+         self.ui_state.main_content_page = MainContentPage::ThemeEditor;
+      }
+      ui.add_space(SPACE);
    }
 
    #[triglyceride::time_event(PROF, "DISPLAY_PATH_TRACER")]
    fn path_tracer(&mut self, ui: &mut Ui) {
-      self.path_tracer.display(ui);
+      self.path_tracer.display(ui, &mut self.graph_editor.selected, &mut self.context.settings);
    }
 
    fn tracer_settings(&mut self, ui: &mut Ui) {
@@ -156,11 +292,24 @@ impl MgsApp {
 
          ui.horizontal(|ui| {
             ui.vertical(|ui| {
-               get_mut!(SETTINGS).current_scene.parthtrace_settings.ui(ui);
+               let scene = &mut self.context.settings.current_scene;
+               scene.parthtrace_settings.ui(ui);
+
+               let switch_to = scene.cameras_ui(ui);
+               if let Some(i) = switch_to { self.path_tracer.switch_camera(i, scene); }
+
+               saved_scenes_ui(ui, &mut self.context.settings);
             });
 
             ui.vertical(|ui| {
                self.image_render_settings(ui);
+
+               let settings = &mut self.context.settings;
+               settings.dynamic_resolution_settings.ui(ui);
+               settings.gizmo_settings.ui(ui);
+               settings.denoise_settings.ui(ui);
+               settings.frame_budget_settings.ui(ui);
+               self.path_tracer.tiled_render_ui(ui, settings);
             });
          });
 
@@ -173,7 +322,7 @@ impl MgsApp {
    fn main_content(&mut self, ui: &mut Ui) {
       match self.ui_state.main_content_page {
          MainContentPage::NodeEditor => {
-            ui.label("node editor");
+            self.graph_editor.ui(ui, &mut self.context.settings.current_scene);
          }
 
          MainContentPage::Stats => {
@@ -186,6 +335,17 @@ impl MgsApp {
                 });
          }
 
+         MainContentPage::RenderQueue => {
+            ScrollArea::vertical()
+                .show(ui, |ui| {
+                   let settings = &self.context.settings;
+                   self.path_tracer.render_queue_ui(ui, &settings.current_scene, &settings.saved_scenes);
+
+                   // moves scroll bar to the right
+                   ui.set_min_width(ui.available_size().x)
+                });
+         }
+
          MainContentPage::Settings => {
             ScrollArea::vertical()
                 .show(ui, |ui| {
@@ -195,16 +355,114 @@ impl MgsApp {
                    ui.set_min_width(ui.available_size().x)
                 });
          }
+
+         MainContentPage::ThemeEditor => {
+            ScrollArea::vertical()
+                .show(ui, |ui| {
+                   self.theme_editor.ui(ui, &mut self.context.settings);
+
+                   // moves scroll bar to the right
+                   ui.set_min_width(ui.available_size().x)
+                });
+         }
       }
    }
 }
 
+fn saved_scenes_ui(ui: &mut Ui, settings: &mut Settings) {
+   let mut load = None;
+   let mut remove = None;
+
+   ui.group(|ui| {
+      ui.horizontal(|ui| {
+         ui.label("Saved scenes");
+         if ui.button("Save current").clicked() {
+            settings.saved_scenes.push(settings.current_scene.clone());
+         }
+      });
+
+      for (i, scene) in settings.saved_scenes.iter().enumerate() {
+         ui.horizontal(|ui| {
+            ui.label(format!("Scene {}, {} objects", i + 1, scene.objects.len()));
+            if ui.button("Load").clicked() { load = Some(i); }
+            if ui.button("🗑").clicked() { remove = Some(i); }
+         });
+      }
+   });
+
+   if let Some(i) = load {
+      // keep the frame counters going so accumulation restarts cleanly
+      let path_set = settings.current_scene.parthtrace_settings;
+      settings.current_scene = settings.saved_scenes[i].clone();
+      settings.current_scene.parthtrace_settings.frame = path_set.frame;
+      settings.current_scene.parthtrace_settings.last_clear_frame = path_set.frame;
+   }
+   if let Some(i) = remove { settings.saved_scenes.remove(i); }
+}
+
+fn frame_time_ui(ui: &mut Ui, time: &TimePackage) {
+   const BINS: usize = 40;
+   let stats = time.frame_stats;
+
+   Grid::new("frame time stats").num_columns(4).show(ui, |ui| {
+      ui.label("p50");
+      ui.label(format!("{:.2} ms", stats.p50));
+      ui.label("Min");
+      ui.label(format!("{:.2} ms", stats.min));
+      ui.end_row();
+
+      ui.label("p95");
+      ui.label(format!("{:.2} ms", stats.p95));
+      ui.label("Max");
+      ui.label(format!("{:.2} ms", stats.max));
+      ui.end_row();
+
+      ui.label("p99");
+      ui.label(format!("{:.2} ms", stats.p99));
+      ui.label("1% low");
+      ui.label(format!("{:.0} fps", stats.one_percent_low));
+      ui.end_row();
+   });
+
+   ui.horizontal(|ui| {
+      let text = format!("{} stutters in the last {} frames, {} total", stats.stutters, time.frame_times.len(), time.total_stutters);
+      if stats.stutters > 0 {
+         ui.colored_label(ui.visuals().warn_fg_color, text);
+      } else {
+         ui.label(text);
+      }
+
+      if let Some(last) = time.last_stutter {
+         ui.label(format!("last {:.1}s ago", last.elapsed().as_secs_f32()));
+      }
+   });
+
+   // frames slower than the stutter threshold are drawn red
+   let (counts, start, width) = time.histogram(BINS);
+   let threshold = stats.p50 * time.stutter_factor;
+   let bars = counts.iter().enumerate().map(|(i, count)| {
+      let x = start + (i as f64 + 0.5) * width;
+      let color = if x > threshold { Color32::RED } else { ui.visuals().selection.bg_fill };
+      Bar::new(x, *count as f64).width(width).fill(color)
+   }).collect();
+
+   Plot::new("frame time histogram")
+       .height(150.0)
+       .allow_drag(false)
+       .allow_scroll(false)
+       .allow_zoom(false)
+       .allow_boxed_zoom(false)
+       .include_y(0.0)
+       .x_axis_label("ms")
+       .show(ui, |plot_ui| plot_ui.bar_chart(BarChart::new(bars)));
+}
+
 /// sub areas
 impl MgsApp {
    #[triglyceride::time_event(PROF, "STATS")]
    fn stats(&mut self, ui: &mut Ui) {
-      get_mut_ref!(SETTINGS, settings);
-      let graph_set = &mut settings.graph_settings;
+      let graph_set = &mut self.context.settings.graph_settings;
+      let time = &mut self.context.time;
 
       let mw = per_width(ui, 0.25);
       ui.set_max_width(mw);
@@ -220,23 +478,23 @@ impl MgsApp {
 
          ui.horizontal(|ui| {
             ui.heading("Application Fps");
-            ui.label(format!("{}", get!(TIME).fps as i32));
+            ui.label(format!("{}", time.fps as i32));
 
             ui.menu_button("...", |ui| {
-               if ui.button("Clear").clicked() { get_mut!(TIME).fps_graphing.clear() };
+               if ui.button("Clear").clicked() { time.fps_graphing.clear() };
 
                if ui.add(Slider::new(&mut fps_graph_set.update_rate, 0.0..=0.99).text("Fps update rate")).changed() {
-                  get_mut!(TIME).fps_update_interval = fps_graph_set.update_rate.sqrt();
+                  time.fps_update_interval = fps_graph_set.update_rate.sqrt();
                }
 
                if ui.add(Slider::new(&mut fps_graph_set.amount, 10..=500).text("Fps graph amount")).changed() {
-                  get_mut!(TIME).fps_amount = fps_graph_set.amount;
+                  time.fps_amount = fps_graph_set.amount;
                }
                ui.add(Slider::new(&mut fps_graph_set.include_upper, 0.0..=2500.0).text("Include at least")).changed();
             });
          });
 
-         let data = get!(TIME).fps_graphing.clone();
+         let data = time.fps_graphing.iter().copied().collect::<Vec<_>>();
 
          let line = Line::new(data).fill(0.0);
          Plot::new("my_plot")
@@ -253,6 +511,19 @@ impl MgsApp {
              .show(ui, |plot_ui| plot_ui.line(line));
       });
 
+      // frame times
+      ui.group(|ui| {
+         ui.horizontal(|ui| {
+            ui.heading("Frame times");
+
+            ui.menu_button("...", |ui| {
+               if ui.button("Reset").clicked() { time.reset_frame_stats() };
+               ui.add(Slider::new(&mut time.stutter_factor, 1.2..=10.0).text("Stutter at x median"));
+            });
+         });
+         frame_time_ui(ui, time);
+      });
+
       // profiler
       if ui.button("Floating").clicked() {
          self.ui_state.floating_profiler_window = ! self.ui_state.floating_profiler_window;
@@ -297,6 +568,19 @@ impl MgsApp {
                ui.label("Active")
             });
 
+            match gpu_profiler.mode {
+               TimestampMode::Encoder => {}
+               TimestampMode::Pass => { ui.label("Timing whole passes only, nested sections need TIMESTAMP_QUERY_INSIDE_ENCODERS"); }
+               TimestampMode::Unsupported => { ui.label("This device doesn't support timestamp queries"); }
+            }
+            if gpu_profiler.dropped() > 0 {
+               ui.colored_label(ui.visuals().warn_fg_color, format!(
+                  "⚠ {} sections didn't fit in {} queries, growing the pool",
+                  gpu_profiler.dropped(),
+                  gpu_profiler.query_count,
+               ));
+            }
+
 
             let mut data_entry = vec![];
             let mut timeings = vec![];
@@ -342,26 +626,34 @@ impl MgsApp {
                       ui.label(timing);
                    }
                 });
+
+            CollapsingHeader::new("Scopes")
+                .default_open(true)
+                .show(ui, |ui| {
+                   gpu_profiler.flame_graph_ui(ui, DEF_WIDTH);
+                   ui.add_space(5.0);
+                   gpu_profiler.scope_tree_ui(ui);
+                });
          }
          else {
             ui.label("Gpu profiler not available on web builds");
          }
       });
 
+      // chrome trace export
+      ui.group(|ui| {
+         self.context.trace.ui(ui);
+      });
+
    }
 
    fn settings_page(&mut self, ui: &mut Ui) {
-      get_mut_ref!(SETTINGS, settings);
+      let settings = &mut self.context.settings;
 
       // theme
       ui.group(|ui| {
          ui.label("Theme");
-         if enum_combination_box(ui, &mut settings.theme, "Theme") { settings.theme.set_theme(ui.ctx()) };
-
-         // unsafe {
-         //    theme_color_picker(ui, &mut TEST_THEME);
-         //    catppuccin_egui::set_theme(ui.ctx(), TEST_THEME);
-         // }
+         if theme_combination_box(ui, settings) { settings.apply_theme(ui.ctx()) };
       });
       ui.add_space(10.0);
 
@@ -400,6 +692,46 @@ impl MgsApp {
       });
       ui.add_space(10.0);
 
+      // adapter
+      ui.group(|ui| {
+         ui.label("Graphics adapter");
+         if self.context.adapters.ui(ui, &mut self.context.settings.adapter_settings) { self.restart(); }
+      });
+      ui.add_space(10.0);
+
+      // keymap
+      ui.group(|ui| {
+         CollapsingHeader::new("Keyboard shortcuts")
+             .show(ui, |ui| {
+                self.context.settings.keymap.ui(ui);
+             });
+      });
+      ui.add_space(10.0);
+
+      // frame pacing
+      ui.group(|ui| {
+         ui.label("Frame pacing");
+
+         let present_mode = &mut self.context.settings.adapter_settings.present_mode;
+         enum_combination_box(ui, present_mode, "Present mode");
+         if *present_mode != self.context.adapters.launched.present_mode {
+            ui.horizontal(|ui| {
+               ui.colored_label(ui.visuals().warn_fg_color, "Applies after a restart");
+               if ui.button("Restart").clicked() { self.restart(); }
+            });
+         }
+
+         self.context.settings.frame_pacing_settings.ui(ui);
+      });
+      ui.add_space(10.0);
+
+      // optional device features
+      ui.group(|ui| {
+         ui.label("Device features");
+         device_features::features_ui(ui, self.context.device_features);
+      });
+      ui.add_space(10.0);
+
       // dev settings
       ui.group(|ui| {
          CollapsingHeader::new("Developer Settings")
@@ -412,10 +744,11 @@ impl MgsApp {
    }
 
    fn image_render_settings(&mut self, ui: &mut Ui) {
-      get_mut_ref!(SETTINGS, settings);
-      let iss = &mut settings.image_size_settings;
+      // the benchmark renders at its own resolution, this would overwrite it every frame
+      let locked = self.path_tracer.benchmark.is_running();
+      let iss = &mut self.context.settings.image_size_settings;
 
-      ui.group(|ui| {
+      ui.add_enabled_ui(!locked, |ui| ui.group(|ui| {
          ui.horizontal(|ui| {
             ui.label("Resolution settings");
          });
@@ -449,8 +782,11 @@ impl MgsApp {
             let aspect = iss.selected_aspect.1 as f32 / iss.selected_aspect.0 as f32;
             ui.add(Slider::new(&mut iss.aspect_scale, 8..=3840).text("Scale"));
 
-            let h = (iss.aspect_scale as f32 * aspect) as u32;
-            let w = (iss.aspect_scale as f32) as u32;
+            let (w, h) = if locked {
+               (iss.width, iss.height)
+            } else {
+               ((iss.aspect_scale as f32) as u32, (iss.aspect_scale as f32 * aspect) as u32)
+            };
 
             ui.group(|ui| {
                ui.label(format!("Dimensions => {w}x{h}"));
@@ -460,7 +796,7 @@ impl MgsApp {
             iss.width = w;
             iss.height = h;
          }
-      });
+      }));
    }
 }
 
@@ -470,33 +806,3 @@ impl MgsApp {
 fn per_width(ui: &mut Ui, per: f32) -> f32 {
    ui.ctx().screen_rect().width() * per
 }
-
-#[allow(dead_code)]
-pub fn theme_color_picker(ui: &mut Ui, theme: &mut catppuccin_egui::Theme) {
-   ui.color_edit_button_srgba(&mut theme.rosewater);
-   ui.color_edit_button_srgba(&mut theme.flamingo);
-   ui.color_edit_button_srgba(&mut theme.pink);
-   ui.color_edit_button_srgba(&mut theme.mauve);
-   ui.color_edit_button_srgba(&mut theme.red);
-   ui.color_edit_button_srgba(&mut theme.maroon);
-   ui.color_edit_button_srgba(&mut theme.peach);
-   ui.color_edit_button_srgba(&mut theme.yellow);
-   ui.color_edit_button_srgba(&mut theme.green);
-   ui.color_edit_button_srgba(&mut theme.teal);
-   ui.color_edit_button_srgba(&mut theme.sky);
-   ui.color_edit_button_srgba(&mut theme.sapphire);
-   ui.color_edit_button_srgba(&mut theme.blue);
-   ui.color_edit_button_srgba(&mut theme.lavender);
-   ui.color_edit_button_srgba(&mut theme.text);
-   ui.color_edit_button_srgba(&mut theme.subtext1);
-   ui.color_edit_button_srgba(&mut theme.subtext0);
-   ui.color_edit_button_srgba(&mut theme.overlay2);
-   ui.color_edit_button_srgba(&mut theme.overlay1);
-   ui.color_edit_button_srgba(&mut theme.overlay0);
-   ui.color_edit_button_srgba(&mut theme.surface2);
-   ui.color_edit_button_srgba(&mut theme.surface1);
-   ui.color_edit_button_srgba(&mut theme.surface0);
-   ui.color_edit_button_srgba(&mut theme.base);
-   ui.color_edit_button_srgba(&mut theme.mantle);
-   ui.color_edit_button_srgba(&mut theme.crust);
-}
\ No newline at end of file
@@ -3,7 +3,9 @@ use std::sync::Arc;
 
 /// Handle to the web app from JavaScript.
 use wasm_bindgen::prelude::wasm_bindgen;
-use wgpu::{Adapter, DeviceDescriptor, Features};
+use wgpu::{Adapter, DeviceDescriptor};
+
+use crate::path_tracer::render_utility::device_features::device_descriptor;
 
 #[cfg(target_arch = "wasm32")]
 #[derive(Clone)]
@@ -30,16 +32,7 @@ impl WebHandle {
    /// Call this once from JavaScript to start your app.
    #[wasm_bindgen]
    pub async fn start(&self, canvas_id: &str) -> Result<(), wasm_bindgen::JsValue> {
-      let device_descriptor_fn: Arc<dyn Fn(&Adapter) -> DeviceDescriptor<'static>> = Arc::new(|_adapter: &Adapter| {
-         DeviceDescriptor {
-            label: Some("wgpu native device desc"),
-            required_features: Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES |
-                Features::TIMESTAMP_QUERY_INSIDE_PASSES |
-                Features::TIMESTAMP_QUERY |
-                Features::TIMESTAMP_QUERY_INSIDE_ENCODERS,
-            required_limits: Default::default(),
-         }
-      });
+      let device_descriptor_fn: Arc<dyn Fn(&Adapter) -> DeviceDescriptor<'static>> = Arc::new(device_descriptor);
 
 
       self.runner